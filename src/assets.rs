@@ -1,3 +1,8 @@
+use std::sync::{
+    Arc, RwLock,
+    atomic::{AtomicU64, Ordering},
+};
+
 use ggez::graphics;
 use resvg::{
     tiny_skia::Pixmap,
@@ -17,6 +22,34 @@ macro_rules! load_png_unwrap {
     };
 }
 
+/// Load the piece image `$name` from the `pieces` directory among the ggez resources if the user
+/// put one there, and otherwise the embedded one.
+macro_rules! load_piece {
+    ($name: literal, $ctx: expr $(,)?) => {
+        load_override($ctx, concat!("/pieces/", $name))
+            .unwrap_or_else(|| load_png_unwrap!(concat!("../assets/pieces/", $name), $ctx))
+    };
+}
+
+/// Load a user provided image overriding an embedded asset. Returns `None` if there is none, or if
+/// it can't be loaded, in which case the embedded asset should be used instead.
+fn load_override(ctx: &mut ggez::Context, path: &str) -> Option<graphics::Image> {
+    if !ctx.fs.exists(path) {
+        return None;
+    }
+
+    match graphics::Image::from_path(ctx, path, true) {
+        Ok(image) => Some(image),
+        Err(error) => {
+            println!(
+                "Could not load asset '{}', using the embedded one instead: {}",
+                path, error
+            );
+            None
+        }
+    }
+}
+
 #[allow(unused_macros)]
 macro_rules! load_svg_unwrap {
     ($path: expr, $ctx: expr, $width: expr, $options: expr $(,)?) => {
@@ -84,6 +117,8 @@ fn load_svg(
     ))
 }
 
+/// The images of one color's pieces. Cloning only clones handles to the images.
+#[derive(Clone)]
 pub struct ChessPieces {
     pawn: graphics::Image,
     knight: graphics::Image,
@@ -94,6 +129,24 @@ pub struct ChessPieces {
 }
 
 impl ChessPieces {
+    pub fn new(
+        pawn: graphics::Image,
+        knight: graphics::Image,
+        bishop: graphics::Image,
+        rook: graphics::Image,
+        queen: graphics::Image,
+        king: graphics::Image,
+    ) -> Self {
+        Self {
+            pawn,
+            knight,
+            bishop,
+            rook,
+            queen,
+            king,
+        }
+    }
+
     pub fn get(&self, kind: PieceKind) -> &graphics::Image {
         match kind {
             PieceKind::Pawn => &self.pawn,
@@ -107,9 +160,15 @@ impl ChessPieces {
 }
 
 /// Stores collection of the pre-loaded chess assets used by the game.
+///
+/// The piece sets can be replaced at runtime through a shared reference, see `set_pieces`. Readers
+/// should fetch a set once with `pieces` and draw from it, rather than locking per piece.
 pub struct Assets {
-    white: ChessPieces,
-    black: ChessPieces,
+    white: RwLock<Arc<ChessPieces>>,
+    black: RwLock<Arc<ChessPieces>>,
+    /// Incremented whenever a piece set is replaced, so that anything derived from the images can
+    /// tell that it's stale.
+    generation: AtomicU64,
 }
 
 impl Assets {
@@ -119,23 +178,26 @@ impl Assets {
 
         println!("Loading assets..");
 
+        let white = ChessPieces::new(
+            load_piece!("pw.png", ctx),
+            load_piece!("nw.png", ctx),
+            load_piece!("bw.png", ctx),
+            load_piece!("rw.png", ctx),
+            load_piece!("qw.png", ctx),
+            load_piece!("kw.png", ctx),
+        );
+        let black = ChessPieces::new(
+            load_piece!("pb.png", ctx),
+            load_piece!("nb.png", ctx),
+            load_piece!("bb.png", ctx),
+            load_piece!("rb.png", ctx),
+            load_piece!("qb.png", ctx),
+            load_piece!("kb.png", ctx),
+        );
         let assets = Self {
-            white: ChessPieces {
-                pawn: load_png_unwrap!("../assets/pieces/pw.png", ctx),
-                knight: load_png_unwrap!("../assets/pieces/nw.png", ctx),
-                bishop: load_png_unwrap!("../assets/pieces/bw.png", ctx),
-                rook: load_png_unwrap!("../assets/pieces/rw.png", ctx),
-                queen: load_png_unwrap!("../assets/pieces/qw.png", ctx),
-                king: load_png_unwrap!("../assets/pieces/kw.png", ctx),
-            },
-            black: ChessPieces {
-                pawn: load_png_unwrap!("../assets/pieces/pb.png", ctx),
-                knight: load_png_unwrap!("../assets/pieces/nb.png", ctx),
-                bishop: load_png_unwrap!("../assets/pieces/bb.png", ctx),
-                rook: load_png_unwrap!("../assets/pieces/rb.png", ctx),
-                queen: load_png_unwrap!("../assets/pieces/qb.png", ctx),
-                king: load_png_unwrap!("../assets/pieces/kb.png", ctx),
-            },
+            white: RwLock::new(Arc::new(white)),
+            black: RwLock::new(Arc::new(black)),
+            generation: AtomicU64::new(0),
         };
 
         println!("Loading complete");
@@ -143,10 +205,37 @@ impl Assets {
         assets
     }
 
-    pub fn piece(&self, color: chess_game::Color, kind: chess_game::PieceKind) -> &graphics::Image {
+    fn lock(&self, color: chess_game::Color) -> &RwLock<Arc<ChessPieces>> {
         match color {
-            chess_game::Color::White => self.white.get(kind),
-            chess_game::Color::Black => self.black.get(kind),
+            chess_game::Color::White => &self.white,
+            chess_game::Color::Black => &self.black,
         }
     }
+
+    /// Returns the current piece set of `color`, which stays valid even if it's replaced meanwhile.
+    pub fn pieces(&self, color: chess_game::Color) -> Arc<ChessPieces> {
+        // The sets are only ever swapped whole, so a poisoned lock still holds a complete set.
+        self.lock(color)
+            .read()
+            .unwrap_or_else(|error| error.into_inner())
+            .clone()
+    }
+
+    pub fn piece(&self, color: chess_game::Color, kind: chess_game::PieceKind) -> graphics::Image {
+        self.pieces(color).get(kind).clone()
+    }
+
+    /// Replace the piece set of `color`, e.g. to switch piece style or re-render at another size.
+    pub fn set_pieces(&self, color: chess_game::Color, pieces: ChessPieces) {
+        *self
+            .lock(color)
+            .write()
+            .unwrap_or_else(|error| error.into_inner()) = Arc::new(pieces);
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Changes whenever a piece set is replaced, see `set_pieces`.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
 }
@@ -0,0 +1,403 @@
+//! Board editor, which lets the user construct an arbitrary position before starting a local game.
+
+use std::{cell::RefCell, sync::Arc};
+
+use ggez::{Context, GameResult, glam, graphics};
+
+use crate::{
+    assets::Assets,
+    chess_game::{self, BoardWrapper, Color, Piece, PieceKind, Position},
+    network::chesstp,
+    palette::PALETTE,
+    typography,
+    ui::{self, ButtonSpecialization, PressState},
+};
+
+/// Distance from the board top edge to the screen edge. Matches the layout of `GameUi`.
+static BOARD_Y_MARGIN: f32 = 40.0;
+/// Size of a single piece in the palette.
+static PALETTE_ENTRY_SIZE: f32 = 70.0;
+/// Gap between the palette entries.
+static PALETTE_GAP: f32 = 10.0;
+
+struct EditorState {
+    board: chesstp::Board,
+    /// The piece from the palette which is placed when a square is left-clicked.
+    selected_piece: Option<Piece>,
+    /// Why the position was rejected the last time the user tried to start the game.
+    error: Option<String>,
+    /// Set once the user has pressed the start button with a playable position.
+    finished: bool,
+}
+
+impl EditorState {
+    fn try_finish(&mut self) {
+        match validate_position(&self.board) {
+            Ok(()) => {
+                self.error = None;
+                self.finished = true;
+            }
+            Err(error) => self.error = Some(error),
+        }
+    }
+}
+
+/// Check that the position can be played, see `chesstp::Board::validate`.
+pub fn validate_position(board: &chesstp::Board) -> Result<(), String> {
+    board.validate().map_err(|error| error.to_string())
+}
+
+struct EditorSquare {
+    position: Position,
+    state: Arc<RefCell<EditorState>>,
+    assets: Arc<Assets>,
+}
+
+impl ButtonSpecialization for EditorSquare {
+    fn draw(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        offset: glam::Vec2,
+        mut bounds: graphics::Rect,
+        press_state: PressState,
+        hovered: bool,
+    ) -> GameResult {
+        bounds.translate(offset);
+
+        chess_game::draw_square_base(
+            ctx,
+            canvas,
+            bounds,
+            self.position,
+            chess_game::Orientation::WhiteAtBottom,
+            press_state,
+            hovered,
+        )?;
+
+        if let Ok(Some(piece)) = self.state.borrow().board.tile(self.position) {
+            chess_game::draw_piece(canvas, &self.assets, piece, bounds);
+        }
+
+        Ok(())
+    }
+
+    fn on_press(&mut self) {
+        let mut state = self.state.borrow_mut();
+        if let Some(piece) = state.selected_piece {
+            state
+                .board
+                .set_tile(self.position, Some(piece))
+                .expect("the editor starts from a complete board");
+        }
+    }
+}
+
+/// One of the 12 pieces which can be picked in the palette.
+struct PaletteEntry {
+    piece: Piece,
+    state: Arc<RefCell<EditorState>>,
+    assets: Arc<Assets>,
+}
+
+impl ButtonSpecialization for PaletteEntry {
+    fn draw(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        offset: glam::Vec2,
+        mut bounds: graphics::Rect,
+        press_state: PressState,
+        hovered: bool,
+    ) -> GameResult {
+        bounds.translate(offset);
+
+        let color = if self.state.borrow().selected_piece == Some(self.piece) {
+            PALETTE.board_square_selected
+        } else {
+            ui::MouseColors::new(PALETTE.button, PALETTE.button_hover, PALETTE.button_active)
+                .get(hovered, press_state)
+        };
+
+        let background = graphics::Mesh::new_rounded_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            bounds,
+            10.0,
+            color,
+        )?;
+        canvas.draw(&background, graphics::DrawParam::new());
+
+        chess_game::draw_piece(canvas, &self.assets, self.piece, bounds);
+
+        Ok(())
+    }
+
+    fn on_press(&mut self) {
+        let mut state = self.state.borrow_mut();
+        state.selected_piece = if state.selected_piece == Some(self.piece) {
+            None
+        } else {
+            Some(self.piece)
+        };
+    }
+}
+
+pub struct BoardEditor {
+    state: Arc<RefCell<EditorState>>,
+    side_bar_bounds: graphics::Rect,
+    /// The window size required to fit the editor, see `GameUi::size`.
+    size: glam::Vec2,
+    square_buttons: Vec<(Position, ui::Button)>,
+    palette_buttons: Vec<ui::Button>,
+    clear_button: ui::Button,
+    start_button: ui::Button,
+}
+
+impl BoardEditor {
+    pub fn new(top_left: glam::Vec2, assets: &Arc<Assets>) -> Self {
+        let state = Arc::new(RefCell::new(EditorState {
+            board: chess::game::game_state::new().into(),
+            selected_piece: None,
+            error: None,
+            finished: false,
+        }));
+
+        let board_bounds = graphics::Rect {
+            x: top_left.x,
+            y: top_left.y + BOARD_Y_MARGIN,
+            w: 100.0 * 8.0,
+            h: 100.0 * 8.0,
+        };
+        let side_bar_bounds = graphics::Rect {
+            x: board_bounds.right(),
+            y: board_bounds.top(),
+            w: chess_game::DEFAULT_SIDE_BAR_WIDTH,
+            h: board_bounds.h,
+        };
+        let size = glam::vec2(
+            board_bounds.w + side_bar_bounds.w,
+            board_bounds.bottom() + BOARD_Y_MARGIN,
+        );
+
+        let square_size = board_bounds.w / 8.0;
+        let square_buttons = (0..8)
+            .flat_map(|row| (0..8).map(move |column| (column, row)))
+            .map(|pair| {
+                let position = Position::from_pair(pair).expect("indices are < 8");
+                let bounds = graphics::Rect {
+                    x: board_bounds.x + position.column() as f32 * square_size,
+                    y: board_bounds.y + (7 - position.row()) as f32 * square_size,
+                    w: square_size,
+                    h: square_size,
+                };
+                let square = EditorSquare {
+                    position,
+                    state: state.clone(),
+                    assets: assets.clone(),
+                };
+
+                (position, ui::Button::new(bounds, square))
+            })
+            .collect();
+
+        // The palette is laid out as one row per piece kind, with white in the left column and
+        // black in the right.
+        let palette_width = PALETTE_ENTRY_SIZE * 2.0 + PALETTE_GAP;
+        let palette_left = side_bar_bounds.center().x - palette_width / 2.0;
+        let palette_top = side_bar_bounds.top() + 30.0;
+        let palette_buttons = [
+            PieceKind::King,
+            PieceKind::Queen,
+            PieceKind::Rook,
+            PieceKind::Bishop,
+            PieceKind::Knight,
+            PieceKind::Pawn,
+        ]
+        .into_iter()
+        .enumerate()
+        .flat_map(|(row, kind)| {
+            [Color::White, Color::Black]
+                .into_iter()
+                .enumerate()
+                .map(move |(column, color)| (row, column, Piece { kind, color }))
+        })
+        .map(|(row, column, piece)| {
+            let bounds = graphics::Rect {
+                x: palette_left + column as f32 * (PALETTE_ENTRY_SIZE + PALETTE_GAP),
+                y: palette_top + row as f32 * (PALETTE_ENTRY_SIZE + PALETTE_GAP),
+                w: PALETTE_ENTRY_SIZE,
+                h: PALETTE_ENTRY_SIZE,
+            };
+            let entry = PaletteEntry {
+                piece,
+                state: state.clone(),
+                assets: assets.clone(),
+            };
+
+            ui::Button::new(bounds, entry)
+        })
+        .collect();
+
+        let button_colors =
+            ui::MouseColors::new(PALETTE.button, PALETTE.button_hover, PALETTE.button_active);
+        let button_top = palette_top + 6.0 * (PALETTE_ENTRY_SIZE + PALETTE_GAP) + 20.0;
+
+        let clear_state = state.clone();
+        let clear_button = ui::Button::new(
+            graphics::Rect {
+                x: side_bar_bounds.center().x - 120.0,
+                y: button_top,
+                w: 240.0,
+                h: 60.0,
+            },
+            ui::RoundedButton::new(10.0, button_colors, move || {
+                clear_state.borrow_mut().board = chesstp::Board::new_empty();
+            }),
+        );
+
+        let start_state = state.clone();
+        let start_button = ui::Button::new(
+            graphics::Rect {
+                x: side_bar_bounds.center().x - 120.0,
+                y: button_top + 60.0 + 20.0,
+                w: 240.0,
+                h: 60.0,
+            },
+            ui::RoundedButton::new(10.0, button_colors, move || {
+                start_state.borrow_mut().try_finish();
+            }),
+        );
+
+        Self {
+            state,
+            side_bar_bounds,
+            size,
+            square_buttons,
+            palette_buttons,
+            clear_button,
+            start_button,
+        }
+    }
+
+    pub fn size(&self) -> glam::Vec2 {
+        self.size
+    }
+
+    fn buttons_mut(&mut self) -> impl Iterator<Item = &mut ui::Button> {
+        self.square_buttons
+            .iter_mut()
+            .map(|(_, button)| button)
+            .chain(self.palette_buttons.iter_mut())
+            .chain([&mut self.clear_button, &mut self.start_button])
+    }
+
+    pub fn update_with_press_state(
+        &mut self,
+        position: glam::Vec2,
+        press_state: PressState,
+    ) -> bool {
+        for button in self.buttons_mut() {
+            if button.update_with_press_state(position, press_state) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Remove the piece from the square at the specified screen position. Returns whether a square
+    /// was hit.
+    pub fn clear_square_at(&mut self, position: glam::Vec2) -> bool {
+        let Some(square) = self
+            .square_buttons
+            .iter()
+            .find(|(_, button)| button.bounds().contains(position))
+            .map(|(square, _)| *square)
+        else {
+            return false;
+        };
+
+        self.state
+            .borrow_mut()
+            .board
+            .set_tile(square, None)
+            .expect("the editor starts from a complete board");
+
+        true
+    }
+
+    pub fn update_with_mouse_position(&mut self, position: glam::Vec2) {
+        for button in self.buttons_mut() {
+            button.update_with_mouse_position(position);
+        }
+    }
+
+    pub fn draw(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        offset: glam::Vec2,
+    ) -> GameResult {
+        for (_, button) in &self.square_buttons {
+            button.draw(ctx, canvas, offset)?;
+        }
+        for button in &self.palette_buttons {
+            button.draw(ctx, canvas, offset)?;
+        }
+
+        for (button, label) in [
+            (&self.clear_button, "Clear board"),
+            (&self.start_button, "Start game"),
+        ] {
+            button.draw(ctx, canvas, offset)?;
+
+            let mut text = graphics::Text::new(label);
+            text.set_scale(graphics::PxScale::from(typography::BODY))
+                .set_layout(graphics::TextLayout::center());
+            canvas.draw(
+                &text,
+                graphics::DrawParam::new().color(PALETTE.text_neutral).dest(
+                    glam::vec2(button.bounds().center().x, button.bounds().center().y) + offset,
+                ),
+            );
+        }
+
+        if let Some(error) = &self.state.borrow().error {
+            let mut text = graphics::Text::new(error.as_str());
+            text.set_scale(graphics::PxScale::from(typography::SMALL))
+                .set_bounds(glam::vec2(self.side_bar_bounds.w - 20.0, f32::INFINITY))
+                .set_layout(graphics::TextLayout {
+                    h_align: graphics::TextAlign::Middle,
+                    v_align: graphics::TextAlign::Begin,
+                });
+            canvas.draw(
+                &text,
+                graphics::DrawParam::new().color(PALETTE.text_subtle).dest(
+                    glam::vec2(
+                        self.side_bar_bounds.center().x,
+                        self.start_button.bounds().bottom() + 20.0,
+                    ) + offset,
+                ),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Returns the constructed position once the user has started the game, with white to move.
+    pub fn take_finished_board(&mut self) -> Option<BoardWrapper> {
+        let state = self.state.borrow();
+        if !state.finished {
+            return None;
+        }
+
+        let mut game = chess::game::game_state::new();
+        state.board.clone().update_game(&mut game);
+
+        let mut board = BoardWrapper::new(game);
+        board.set_turn(Color::White);
+
+        Some(board)
+    }
+}
@@ -1,13 +1,20 @@
-use std::{cell::RefCell, fmt::Debug, sync::Arc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    sync::{Arc, mpsc},
+    time::{Duration, Instant},
+};
 
 use ggez::{Context, GameResult, glam, graphics, mint};
 
 use crate::{
     assets::Assets,
-    chess_graphics::{BorderRadii, RoundedRectangle, SizedImage},
+    chess_graphics::{self, BorderRadii, RoundedRectangle, SizedImage},
     network::{self, chesstp},
-    palette::PALETTE,
+    palette::{LabelColor, PALETTE, contrasting_text_color},
     rect::RectUtils,
+    typography,
     ui::{self, ButtonSpecialization, PressState},
 };
 
@@ -15,6 +22,94 @@ use crate::{
 static BOARD_CORNER_RADIUS: f32 = 15.0;
 /// Distance from the board top edge to the screen edge.
 static BOARD_Y_MARGIN: f32 = 40.0;
+/// Width of the frame around the board, see `GameUi::set_board_frame`.
+static BOARD_FRAME_WIDTH: f32 = 3.0;
+/// Width of the sidebar next to the board, unless changed with `GameUi::set_side_bar_width`.
+pub(crate) static DEFAULT_SIDE_BAR_WIDTH: f32 = 300.0;
+/// Distance from the board bottom edge to the bottom player label.
+static BOTTOM_LABEL_GAP: f32 = 8.0;
+/// Height of the bar showing whose turn it is in compact mode, see `GameUi::set_compact`.
+static TURN_BAR_HEIGHT: f32 = 6.0;
+/// Distance from the bottom player label to the turn bar.
+static TURN_BAR_GAP: f32 = 8.0;
+/// How long each step of the waiting indicator animation is shown.
+static WAITING_DOT_INTERVAL: Duration = Duration::from_millis(400);
+/// How long the turn indicator flashes for when the remote's move makes it the local player's
+/// turn.
+static TURN_FLASH_DURATION: Duration = Duration::from_secs(1);
+/// How long the board takes to turn half a turn when its orientation changes.
+static FLIP_DURATION: Duration = Duration::from_millis(400);
+/// How many times a position has to occur for a draw by repetition to be claimable.
+static CLAIMABLE_REPETITIONS: u32 = 3;
+/// How many times a position has to occur for the game to be drawn by repetition without a claim.
+static AUTOMATIC_REPETITIONS: u32 = 5;
+/// How many half moves without a capture or pawn move make a draw claimable, i.e. fifty moves
+/// each.
+static CLAIMABLE_MOVE_LIMIT: u32 = 100;
+/// How many half moves without a capture or pawn move draw the game without a claim, i.e.
+/// seventy-five moves each.
+static AUTOMATIC_MOVE_LIMIT: u32 = 150;
+/// How long warnings about single events, like a malformed message, stay in the sidebar.
+static TRANSIENT_WARNING_DURATION: Duration = Duration::from_secs(5);
+
+/// How far text like the player labels may shrink to fit its area, relative to its normal size,
+/// before the end of it is elided instead.
+static MIN_FITTED_TEXT_SCALE: f32 = 0.6;
+/// Space kept free on either side of text which is fitted to an area, like the sidebar.
+static TEXT_MARGIN: f32 = 10.0;
+
+/// Width of the ring around capturable pieces, relative to the square width.
+static CAPTURE_RING_WIDTH: f32 = 0.1;
+/// Width of the thinner ring drawn for en passant captures, relative to the square width.
+static EN_PASSANT_RING_WIDTH: f32 = 0.06;
+/// Radius of the dot on empty destination squares, relative to the square width.
+static QUIET_MOVE_DOT_RADIUS: f32 = 0.2;
+
+/// What pawns can be promoted to, in the order the promotion chooser offers them.
+static PROMOTION_CHOICES: [PieceKind; 4] = [
+    PieceKind::Queen,
+    PieceKind::Rook,
+    PieceKind::Bishop,
+    PieceKind::Knight,
+];
+
+/// Every kind of piece, in the order their draw batches are created.
+static PIECE_KINDS: [PieceKind; 6] = [
+    PieceKind::Pawn,
+    PieceKind::Knight,
+    PieceKind::Bishop,
+    PieceKind::Rook,
+    PieceKind::Queen,
+    PieceKind::King,
+];
+
+/// Offsets a knight can jump by.
+static KNIGHT_OFFSETS: [(i8, i8); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+/// Offsets to the squares surrounding a square, which is also how far a king moves.
+static KING_OFFSETS: [(i8, i8); 8] = [
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+];
+static ROOK_DIRECTIONS: [(i8, i8); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+static BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+
+#[cfg(test)]
+mod tests;
 
 /// Represents a coordinate on a chess board. Wrapper around u8 guaranteed to be within 0..8
 /// (exclusive).
@@ -55,6 +150,12 @@ impl Position {
     pub fn new(column: u8, row: u8) -> Option<Self> {
         Self::from_pair((column, row))
     }
+    /// Iterate over every square of the board, starting with a1, b1, ...
+    pub fn all() -> impl Iterator<Item = Self> {
+        (0..8).flat_map(|row| {
+            (0..8).map(move |column| Self::new(column, row).expect("indices are < 8"))
+        })
+    }
     pub fn from_pair(pair: (u8, u8)) -> Option<Self> {
         Some(Position {
             column: PositionIndex::new(pair.0)?,
@@ -103,11 +204,19 @@ impl Position {
         }
     }
 
+    /// The lowercase letter of the file (column), 'a' through 'h'.
+    pub fn file_char(self) -> char {
+        (b'a' + self.column.0) as char
+    }
+
+    /// The digit of the rank (row), '1' through '8'.
+    pub fn rank_char(self) -> char {
+        (b'1' + self.row.0) as char
+    }
+
     pub fn to_string(&self, uppercase: bool) -> String {
-        let column = char::from_digit((self.column.0 as u32) + 10, 18)
-            .expect("Position to hold value in valid range");
-        let row = char::from_digit(self.row.0 as u32 + 1, 10)
-            .expect("Position to hold value in valid range");
+        let column = self.file_char();
+        let row = self.rank_char();
 
         format!(
             "{}{}",
@@ -138,12 +247,7 @@ impl From<Position> for mint::Vector2<f32> {
 
 impl Debug for Position {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let column = char::from_digit((self.column.0 as u32) + 10, 18)
-            .expect("Position to hold value in valid range");
-        let row = char::from_digit(self.row.0 as u32 + 1, 10)
-            .expect("Position to hold value in valid range");
-
-        write!(f, "{}{}", column, row)
+        write!(f, "{}{}", self.file_char(), self.rank_char())
     }
 }
 
@@ -161,6 +265,23 @@ impl Color {
             Color::Black => Color::White,
         }
     }
+
+    /// The character used for the color in FEN and by the chess library, i.e. 'w' or 'b'.
+    pub fn to_fen_char(self) -> char {
+        match self {
+            Color::White => 'w',
+            Color::Black => 'b',
+        }
+    }
+
+    /// Inverse of `to_fen_char`.
+    pub fn from_fen_char(char_: char) -> Option<Self> {
+        match char_ {
+            'w' => Some(Color::White),
+            'b' => Some(Color::Black),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
@@ -173,25 +294,385 @@ pub enum PieceKind {
     King,
 }
 
+impl PieceKind {
+    /// Parse the name the chess library uses for the kind, e.g. "knight".
+    pub fn from_library_name(name: &str) -> Option<Self> {
+        match name {
+            "pawn" => Some(Self::Pawn),
+            "knight" => Some(Self::Knight),
+            "bishop" => Some(Self::Bishop),
+            "rook" => Some(Self::Rook),
+            "queen" => Some(Self::Queen),
+            "king" => Some(Self::King),
+            _ => None,
+        }
+    }
+
+    /// Parse the letter used for the kind in FEN, in either case.
+    pub fn from_fen_char(char_: char) -> Option<Self> {
+        match char_.to_ascii_lowercase() {
+            'p' => Some(Self::Pawn),
+            'n' => Some(Self::Knight),
+            'b' => Some(Self::Bishop),
+            'r' => Some(Self::Rook),
+            'q' => Some(Self::Queen),
+            'k' => Some(Self::King),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Piece {
     pub kind: PieceKind,
     pub color: Color,
 }
 
+/// How much each kind of piece is worth when counting material, see `BoardWrapper::material`.
+/// Kings are never counted. Defaults to the usual 1/3/3/5/9.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PieceValues {
+    pawn: f32,
+    knight: f32,
+    bishop: f32,
+    rook: f32,
+    queen: f32,
+}
+
+impl Default for PieceValues {
+    fn default() -> Self {
+        Self {
+            pawn: 1.0,
+            knight: 3.0,
+            bishop: 3.0,
+            rook: 5.0,
+            queen: 9.0,
+        }
+    }
+}
+
+impl PieceValues {
+    /// Returns an error naming the first value which is negative or not a number.
+    pub fn new(pawn: f32, knight: f32, bishop: f32, rook: f32, queen: f32) -> Result<Self, String> {
+        for (name, value) in [
+            ("pawn", pawn),
+            ("knight", knight),
+            ("bishop", bishop),
+            ("rook", rook),
+            ("queen", queen),
+        ] {
+            if value.is_nan() || value < 0.0 {
+                return Err(format!(
+                    "Value of {} must be non-negative, got {}",
+                    name, value
+                ));
+            }
+        }
+
+        Ok(Self {
+            pawn,
+            knight,
+            bishop,
+            rook,
+            queen,
+        })
+    }
+
+    pub fn value(&self, kind: PieceKind) -> f32 {
+        match kind {
+            PieceKind::Pawn => self.pawn,
+            PieceKind::Knight => self.knight,
+            PieceKind::Bishop => self.bishop,
+            PieceKind::Rook => self.rook,
+            PieceKind::Queen => self.queen,
+            PieceKind::King => 0.0,
+        }
+    }
+}
+
+/// Draws the background tile and coordinate labels of the board square at `position`, returning
+/// the corner radii used so that overlays can match its shape. `bounds` should already be
+/// translated into screen space.
+pub(crate) fn draw_square_base(
+    ctx: &mut Context,
+    canvas: &mut graphics::Canvas,
+    bounds: graphics::Rect,
+    position: Position,
+    orientation: Orientation,
+    press_state: PressState,
+    hovered: bool,
+) -> GameResult<BorderRadii> {
+    let to_actual_color = move |square_color: Color| match square_color {
+        Color::White => match press_state {
+            PressState::Pressed => PALETTE.board_square_white_active,
+            PressState::Released if hovered => PALETTE.board_square_white_hover,
+            PressState::Released => PALETTE.board_square_white,
+        },
+        Color::Black => match press_state {
+            PressState::Pressed => PALETTE.board_square_black_active,
+            PressState::Released if hovered => PALETTE.board_square_black_hover,
+            PressState::Released => PALETTE.board_square_black,
+        },
+    };
+
+    let square_color = if (position.column() + position.row()) % 2 == 0 {
+        Color::Black
+    } else {
+        Color::White
+    };
+
+    let bg_color = to_actual_color(square_color);
+    let label_color = match PALETTE.board_label {
+        LabelColor::OppositeSquare => to_actual_color(square_color.opposite()),
+        LabelColor::Fixed(color) => color,
+        LabelColor::Auto => contrasting_text_color(bg_color),
+    };
+
+    let (screen_column, screen_row) = orientation.screen_indices(position);
+
+    let corner_radii = match (screen_column, screen_row) {
+        (0, 0) => BorderRadii {
+            top_left: BOARD_CORNER_RADIUS,
+            ..BorderRadii::zero()
+        },
+        (7, 0) => BorderRadii {
+            top_right: BOARD_CORNER_RADIUS,
+            ..BorderRadii::zero()
+        },
+        (0, 7) => BorderRadii {
+            bottom_left: BOARD_CORNER_RADIUS,
+            ..BorderRadii::zero()
+        },
+        (7, 7) => BorderRadii {
+            bottom_right: BOARD_CORNER_RADIUS,
+            ..BorderRadii::zero()
+        },
+        _ => BorderRadii::zero(),
+    };
+
+    // Draw bg tile.
+    let mesh = RoundedRectangle::new(
+        ctx,
+        graphics::DrawMode::fill(),
+        bounds,
+        corner_radii,
+        bg_color,
+    )?;
+    canvas.draw(&mesh, graphics::DrawParam::new());
+
+    // Draw label text along the left and bottom edges of the board, whichever side is at the
+    // bottom.
+    if screen_column == 0 {
+        // Showing row number
+        let string = position.rank_char().to_string();
+
+        // Note: that last offset is arbitrary
+        let text_position = glam::vec2(bounds.x + 15.0, bounds.y + 15.0 + 6.0);
+
+        let mut text = graphics::Text::new(string);
+        // TODO: Choose font. See ggez text example for how to load it.
+        text.set_scale(typography::BODY)
+            .set_bounds(glam::vec2(30.0, 30.0))
+            .set_layout(graphics::TextLayout::center());
+
+        canvas.draw(
+            &text,
+            graphics::DrawParam::new()
+                .dest(text_position)
+                .color(label_color),
+        );
+    }
+    if screen_row == 7 {
+        // Showing column number
+        let string = position.file_char().to_string();
+
+        let text_position = glam::vec2(bounds.right() - 15.0, bounds.bottom() - 15.0);
+
+        let mut text = graphics::Text::new(string);
+        // TODO: Choose font. See ggez text example for how to load it.
+        text.set_scale(typography::BODY)
+            .set_bounds(glam::vec2(30.0, 30.0))
+            .set_layout(graphics::TextLayout::center());
+
+        canvas.draw(
+            &text,
+            graphics::DrawParam::new()
+                .dest(text_position)
+                .color(label_color),
+        );
+    }
+
+    Ok(corner_radii)
+}
+
+/// How many draw calls the pieces on `board` take per frame, as the number with one batch per kind
+/// and color of piece, see `GameUi::piece_batches`, and the number with one per piece.
+fn piece_draw_calls(board: &BoardWrapper) -> (usize, usize) {
+    let pieces = Position::all()
+        .filter_map(|position| board.at(position))
+        .collect::<Vec<_>>();
+    let batches = pieces.iter().collect::<HashSet<_>>().len();
+    (batches, pieces.len())
+}
+
+/// Returns the bounds of the square at `position` within `board_bounds` when the board is drawn
+/// with `orientation`.
+fn square_bounds(
+    board_bounds: graphics::Rect,
+    position: Position,
+    orientation: Orientation,
+) -> graphics::Rect {
+    let (screen_column, screen_row) = orientation.screen_indices(position);
+    let position_indices = glam::vec2(screen_column as f32, screen_row as f32);
+
+    let mut square_bounds = board_bounds.clone();
+    square_bounds.scale(1.0 / 8.0, 1.0 / 8.0);
+
+    let square_size: glam::Vec2 = square_bounds.size().into();
+    square_bounds.translate(position_indices * square_size);
+
+    square_bounds
+}
+
+/// Returns the bounds of the `index`th choice of the promotion chooser for a pawn promoting on
+/// `dest`. The choices are stacked from `dest` towards the middle of the board.
+fn promotion_choice_bounds(
+    board_bounds: graphics::Rect,
+    dest: Position,
+    orientation: Orientation,
+    index: u8,
+) -> graphics::Rect {
+    let row = if dest.row() == 7 {
+        dest.row() - index
+    } else {
+        dest.row() + index
+    };
+    let position = Position::new(dest.column(), row).expect("there are only 4 choices");
+    square_bounds(board_bounds, position, orientation)
+}
+
+/// Sizes of the move destination markers for a square of width `square_width`, as the ring width,
+/// ring radius and dot radius. The ring lies just inside the square edges.
+fn destination_marker_sizes(square_width: f32, is_en_passant: bool) -> (f32, f32, f32) {
+    let ring_width = square_width
+        * if is_en_passant {
+            EN_PASSANT_RING_WIDTH
+        } else {
+            CAPTURE_RING_WIDTH
+        };
+    let ring_radius = square_width / 2.0 - ring_width / 2.0;
+
+    (
+        ring_width,
+        ring_radius,
+        square_width * QUIET_MOVE_DOT_RADIUS,
+    )
+}
+
+/// Draws a large title with an optional smaller subtitle below it, centered at the top of `area`,
+/// like "White / to move" in the sidebar. Both shrink to fit the width of `area`. Returns the
+/// subtitle text so that more can be drawn next to it.
+fn draw_sidebar_headline(
+    ctx: &Context,
+    canvas: &mut graphics::Canvas,
+    area: graphics::Rect,
+    offset: glam::Vec2,
+    title: &str,
+    title_color: graphics::Color,
+    subtitle: Option<&str>,
+) -> GameResult<Option<graphics::Text>> {
+    let layout = graphics::TextLayout {
+        h_align: graphics::TextAlign::Middle,
+        v_align: graphics::TextAlign::Begin,
+    };
+    let max_width = area.w - 2.0 * TEXT_MARGIN;
+
+    let mut title_text = fitted_text(ctx, title, typography::HEADING, max_width)?;
+    title_text.set_layout(layout);
+    canvas.draw(
+        &title_text,
+        graphics::DrawParam::new()
+            .color(title_color)
+            .dest(glam::vec2(area.center().x, area.top()) + offset),
+    );
+
+    let Some(subtitle) = subtitle else {
+        return Ok(None);
+    };
+    let mut subtitle_text = fitted_text(ctx, subtitle, typography::BODY, max_width)?;
+    subtitle_text.set_layout(layout);
+    canvas.draw(
+        &subtitle_text,
+        graphics::DrawParam::new()
+            .color(PALETTE.text_subtle)
+            .dest(glam::vec2(area.center().x, area.top() + typography::HEADING) + offset),
+    );
+
+    Ok(Some(subtitle_text))
+}
+
+/// Creates a text of `content` which is at most `max_width` wide, by shrinking it from `scale` down
+/// to `MIN_FITTED_TEXT_SCALE` of it, and eliding the end if it still doesn't fit.
+fn fitted_text(
+    ctx: &Context,
+    content: &str,
+    scale: f32,
+    max_width: f32,
+) -> GameResult<graphics::Text> {
+    let mut text = graphics::Text::new(content);
+    text.set_scale(graphics::PxScale::from(scale));
+    let width = text.measure(ctx)?.x;
+    if width <= max_width {
+        return Ok(text);
+    }
+
+    let fitted_scale = (scale * max_width / width).max(scale * MIN_FITTED_TEXT_SCALE);
+    let elided = elide_to_fit(content, max_width, |candidate| {
+        let mut candidate = graphics::Text::new(candidate);
+        candidate.set_scale(graphics::PxScale::from(fitted_scale));
+        Ok(candidate.measure(ctx)?.x)
+    })?;
+
+    let mut text = graphics::Text::new(elided);
+    text.set_scale(graphics::PxScale::from(fitted_scale));
+    Ok(text)
+}
+
+/// Returns the bounds of a piece graphic, slightly inset within the square `bounds`.
+fn piece_bounds(bounds: graphics::Rect) -> graphics::Rect {
+    static PIECE_SCALE: f32 = 0.9;
+
+    let mut piece_bounds = bounds.clone();
+    piece_bounds.scale(PIECE_SCALE, PIECE_SCALE);
+    let size: glam::Vec2 = bounds.size().into();
+    piece_bounds.translate((size - size * PIECE_SCALE) / 2.0);
+
+    piece_bounds
+}
+
+/// Draws the graphic of `piece` slightly inset within the square `bounds`.
+pub(crate) fn draw_piece(
+    canvas: &mut graphics::Canvas,
+    assets: &Assets,
+    piece: Piece,
+    bounds: graphics::Rect,
+) {
+    let piece_bounds = piece_bounds(bounds);
+    let image = assets.piece(piece.color, piece.kind);
+    let sized_image = SizedImage::new(&image, piece_bounds.size().into());
+    canvas.draw(&sized_image, piece_bounds.point());
+}
+
+/// A board square. The piece standing on it is drawn separately by `GameUi`, which batches the
+/// pieces of each kind into a single draw call.
 struct Square {
     position: Position,
     state: Arc<RefCell<GameState>>,
-    assets: Arc<Assets>,
 }
 
 impl Square {
-    pub fn new(position: Position, state: Arc<RefCell<GameState>>, assets: Arc<Assets>) -> Self {
-        Self {
-            position,
-            state,
-            assets,
-        }
+    pub fn new(position: Position, state: Arc<RefCell<GameState>>) -> Self {
+        Self { position, state }
     }
 }
 
@@ -211,125 +692,62 @@ impl ButtonSpecialization for Square {
         // // self.game.get_mut();
         bounds.translate(offset);
 
-        let to_actual_color = move |square_color: Color| match square_color {
-            Color::White => match press_state {
-                PressState::Pressed => PALETTE.board_square_white_active,
-                PressState::Released if hovered => PALETTE.board_square_white_hover,
-                PressState::Released => PALETTE.board_square_white,
-            },
-            Color::Black => match press_state {
-                PressState::Pressed => PALETTE.board_square_black_active,
-                PressState::Released if hovered => PALETTE.board_square_black_hover,
-                PressState::Released => PALETTE.board_square_black,
-            },
-        };
-
-        let square_color = if (self.position.column() + self.position.row()) % 2 == 0 {
-            Color::Black
-        } else {
-            Color::White
-        };
-
-        let bg_color = to_actual_color(square_color);
-
-        let row = match self.state.borrow().connection {
-            network::GameConnection::Remote(network::ConnectionType::Server, _, _) => {
-                7 - self.position.row()
-            }
-            _ => self.position.row(),
-        };
-
-        let corner_radii = match (self.position.column(), row) {
-            (0, 7) => BorderRadii {
-                top_left: BOARD_CORNER_RADIUS,
-                ..BorderRadii::zero()
-            },
-            (7, 7) => BorderRadii {
-                top_right: BOARD_CORNER_RADIUS,
-                ..BorderRadii::zero()
-            },
-            (0, 0) => BorderRadii {
-                bottom_left: BOARD_CORNER_RADIUS,
-                ..BorderRadii::zero()
-            },
-            (7, 0) => BorderRadii {
-                bottom_right: BOARD_CORNER_RADIUS,
-                ..BorderRadii::zero()
-            },
-            _ => BorderRadii::zero(),
-        };
-
-        // Draw bg tile.
-        let mesh = RoundedRectangle::new(
+        let orientation = self.state.borrow().orientation;
+        let corner_radii = draw_square_base(
             ctx,
-            graphics::DrawMode::fill(),
+            canvas,
             bounds,
-            corner_radii,
-            bg_color,
+            self.position,
+            orientation,
+            press_state,
+            hovered,
         )?;
-        canvas.draw(&mesh, graphics::DrawParam::new());
-
-        // Draw label text.
-        if self.position.column() == 0 {
-            // Showing row number
-            let string = format!("{}", self.position.row() + 1);
-
-            // Note: that last offset is arbitrary
-            let position = glam::vec2(bounds.x + 15.0, bounds.y + 15.0 + 6.0);
-
-            let mut text = graphics::Text::new(string);
-            // TODO: Choose font. See ggez text example for how to load it.
-            text.set_scale(30.0)
-                .set_bounds(glam::vec2(30.0, 30.0))
-                .set_layout(graphics::TextLayout::center());
-
-            canvas.draw(
-                &text,
-                graphics::DrawParam::new()
-                    .dest(position)
-                    .color(to_actual_color(square_color.opposite())),
-            );
-        }
-        if self.position.row() == 0 {
-            // Showing column number
-            let string = format!(
-                "{}",
-                char::from_digit(self.position.column() as u32 + 10, 20)
-                    .expect("surely my math works")
-            );
-
-            let position = glam::vec2(bounds.right() - 15.0, bounds.bottom() - 15.0);
-
-            let mut text = graphics::Text::new(string);
-            // TODO: Choose font. See ggez text example for how to load it.
-            text.set_scale(30.0)
-                .set_bounds(glam::vec2(30.0, 30.0))
-                .set_layout(graphics::TextLayout::center());
-
-            canvas.draw(
-                &text,
-                graphics::DrawParam::new()
-                    .dest(position)
-                    .color(to_actual_color(square_color.opposite())),
-            );
-        }
 
-        // Draw highlight if selected.
+        // Draw tint if attacked by the opponent of the hovered king.
         if self
             .state
             .borrow()
-            .last_move
-            .into_iter()
-            .map(|(source, dest)| [source, dest])
-            .flatten()
-            .chain(match self.state.borrow().turn_phase {
-                TurnPhase::SelectDest(source) => Some(source),
-                _ => None,
-            })
-            .any(|square| self.position == square)
+            .threatened_squares
+            .contains(&self.position)
         {
-            let mut color = PALETTE.board_square_selected;
-            color.a = 0.7;
+            let mesh = RoundedRectangle::new(
+                ctx,
+                graphics::DrawMode::fill(),
+                bounds,
+                corner_radii,
+                PALETTE.threatened_square_overlay,
+            )?;
+            canvas.draw(&mesh, graphics::DrawParam::new());
+        }
+
+        // Draw highlight if selected or staged, or else if a king in check, or else if part of the
+        // last move.
+        let highlight = {
+            let state = self.state.borrow();
+            match state.turn_phase {
+                TurnPhase::SelectDest(source)
+                | TurnPhase::ConfirmMove { source, .. }
+                | TurnPhase::ChoosePromotion { source, .. }
+                    if source == self.position =>
+                {
+                    Some(PALETTE.selection_highlight)
+                }
+                TurnPhase::ConfirmMove { dest, .. } | TurnPhase::ChoosePromotion { dest, .. }
+                    if dest == self.position =>
+                {
+                    Some(PALETTE.staged_move_highlight)
+                }
+                _ if state.checked_king == Some(self.position) => Some(PALETTE.check_highlight),
+                _ if state.last_move.is_some_and(|(source, dest)| {
+                    self.position == source || self.position == dest
+                }) =>
+                {
+                    Some(PALETTE.last_move_highlight)
+                }
+                _ => None,
+            }
+        };
+        if let Some(color) = highlight {
             let mesh = RoundedRectangle::new(
                 ctx,
                 graphics::DrawMode::fill(),
@@ -340,76 +758,266 @@ impl ButtonSpecialization for Square {
             canvas.draw(&mesh, graphics::DrawParam::new());
         }
 
-        // Draw potential destination highlight.
-        let phase = self.state.borrow().turn_phase;
-        let is_potential_dest = phase.source_square().is_some_and(|source| {
-            self.state
-                .borrow_mut()
-                .board
-                .valid_moves(source)
-                .any(|dest| self.position == dest)
-        });
-        if is_potential_dest {
-            let mesh = if self.state.borrow().board.at(self.position).is_some() {
-                // Square is occupied.
-                graphics::Mesh::new_circle(
+        // Draw potential destination highlight: a ring for captures, a dot for quiet moves and
+        // both for en passant, where the captured pawn isn't on the destination. Moves giving
+        // check use a separate color.
+        let state = self.state.borrow();
+        if let (TurnPhase::SelectDest(source), true) = (
+            state.turn_phase,
+            state.potential_dests.contains(&self.position),
+        ) {
+            let is_en_passant = state.board.is_en_passant(source, self.position);
+            let is_capture = is_en_passant || state.board.at(self.position).is_some();
+            let color = if state.checking_dests.contains(&self.position) {
+                PALETTE.checking_move_overlay
+            } else if is_capture {
+                PALETTE.capture_target_overlay
+            } else {
+                PALETTE.quiet_move_overlay
+            };
+
+            let (ring_width, ring_radius, dot_radius) =
+                destination_marker_sizes(bounds.w, is_en_passant);
+            if is_capture {
+                let ring = graphics::Mesh::new_circle(
                     ctx,
-                    graphics::DrawMode::stroke(10.0),
+                    graphics::DrawMode::stroke(ring_width),
                     bounds.center(),
-                    bounds.w / 2.0 - 5.0,
-                    0.001,
-                    PALETTE.highlight_subtle_overlay,
-                )?
-            } else {
-                // Square is empty.
-                graphics::Mesh::new_circle(
+                    ring_radius,
+                    chess_graphics::default_tolerance(ring_radius),
+                    color,
+                )?;
+                canvas.draw(&ring, graphics::DrawParam::new());
+            }
+            if !is_capture || is_en_passant {
+                let dot = graphics::Mesh::new_circle(
                     ctx,
                     graphics::DrawMode::fill(),
                     bounds.center(),
-                    20.0,
-                    0.001,
-                    PALETTE.highlight_subtle_overlay,
-                )?
-            };
-            canvas.draw(&mesh, graphics::DrawParam::new());
+                    dot_radius,
+                    chess_graphics::default_tolerance(dot_radius),
+                    color,
+                )?;
+                canvas.draw(&dot, graphics::DrawParam::new());
+            }
         }
 
-        // Draw piece graphic.
-        if let Some(piece) = self.state.borrow().board.at(self.position) {
-            static PIECE_SCALE: f32 = 0.9;
+        Ok(())
+    }
+
+    fn on_press(&mut self) {
+        self.state.borrow_mut().select_square(self.position);
+    }
+}
 
-            let mut piece_bounds = bounds.clone();
-            piece_bounds.scale(PIECE_SCALE, PIECE_SCALE);
-            let size: glam::Vec2 = bounds.size().into();
-            piece_bounds.translate((size - size * PIECE_SCALE) / 2.0);
-            let image = SizedImage::new(
-                self.assets.piece(piece.color, piece.kind),
-                piece_bounds.size().into(),
-            );
-            canvas.draw(&image, piece_bounds.point());
-        }
+/// One of the pieces offered by the promotion chooser, see `TurnPhase::ChoosePromotion`.
+struct PromotionChoice {
+    kind: PieceKind,
+    state: Arc<RefCell<GameState>>,
+    assets: Arc<Assets>,
+}
+
+impl ButtonSpecialization for PromotionChoice {
+    fn draw(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        offset: glam::Vec2,
+        mut bounds: graphics::Rect,
+        press_state: PressState,
+        hovered: bool,
+    ) -> GameResult {
+        bounds.translate(offset);
+
+        let colors =
+            ui::MouseColors::new(PALETTE.button, PALETTE.button_hover, PALETTE.button_active);
+        let background = graphics::Mesh::new_rounded_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            bounds,
+            10.0,
+            colors.get(hovered, press_state),
+        )?;
+        canvas.draw(&background, graphics::DrawParam::new());
+
+        let color = self.state.borrow().board.turn();
+        draw_piece(
+            canvas,
+            &self.assets,
+            Piece {
+                kind: self.kind,
+                color,
+            },
+            bounds,
+        );
 
         Ok(())
     }
 
     fn on_press(&mut self) {
-        self.state.borrow_mut().select_square(self.position);
+        self.state.borrow_mut().choose_promotion(self.kind);
+    }
+}
+
+/// Which side of the board is drawn at the bottom of the screen.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Orientation {
+    WhiteAtBottom,
+    BlackAtBottom,
+}
+
+impl Orientation {
+    /// The orientation which shows `color` at the bottom, i.e. how that player sees the board.
+    pub fn facing(color: Color) -> Self {
+        match color {
+            Color::White => Self::WhiteAtBottom,
+            Color::Black => Self::BlackAtBottom,
+        }
+    }
+
+    /// The orientation with the other color at the bottom.
+    pub fn flipped(self) -> Self {
+        match self {
+            Self::WhiteAtBottom => Self::BlackAtBottom,
+            Self::BlackAtBottom => Self::WhiteAtBottom,
+        }
+    }
+
+    /// Where `position` is drawn, as the column counted from the left and the row counted from the
+    /// top of the screen. Turning the board to the other orientation rotates it half a turn, so
+    /// a1 is in the bottom left corner for white and in the top right corner for black.
+    pub fn screen_indices(self, position: Position) -> (u8, u8) {
+        match self {
+            Self::WhiteAtBottom => (position.column(), 7 - position.row()),
+            Self::BlackAtBottom => (7 - position.column(), position.row()),
+        }
     }
 }
 
+/// The result of a move which was played.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum MoveOutcome {
     Valid,
+    /// The move put the opponent in check.
     Check,
+    /// The move put the opponent in checkmate, ending the game.
+    Checkmate,
+}
+
+/// Why a game was drawn.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DrawReason {
+    /// The players agreed to a draw. Is also assumed when the remote declares a draw for which no
+    /// other reason applies, since chesstp doesn't carry the reason.
+    Agreement,
+    /// The side to move has no legal moves but isn't in check.
+    Stalemate,
+    /// The same position occurred `CLAIMABLE_REPETITIONS` times and a draw was claimed, or
+    /// `AUTOMATIC_REPETITIONS` times.
+    Repetition,
+    /// `CLAIMABLE_MOVE_LIMIT` half moves were played without a capture or pawn move and a draw was
+    /// claimed, or `AUTOMATIC_MOVE_LIMIT` were.
+    FiftyMove,
+    /// Neither side has enough pieces left to checkmate.
+    InsufficientMaterial,
+}
+
+impl DrawReason {
+    /// Completes "Draw by ...".
+    pub fn description(&self) -> &'static str {
+        match self {
+            DrawReason::Agreement => "agreement",
+            DrawReason::Stalemate => "stalemate",
+            DrawReason::Repetition => "repetition",
+            DrawReason::FiftyMove => "the fifty-move rule",
+            DrawReason::InsufficientMaterial => "insufficient material",
+        }
+    }
+}
+
+/// Why the game ended, which is shown in the game-over banner.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EndReason {
     Checkmate,
+    Draw(DrawReason),
+    /// A player left an ongoing game.
+    Resignation,
+    /// A player ran out of time. Only ever received, since there is no clock yet.
+    Timeout,
+}
+
+impl EndReason {
+    pub fn title(&self) -> &'static str {
+        match self {
+            EndReason::Checkmate => "Checkmate",
+            EndReason::Draw(_) => "Draw",
+            EndReason::Resignation => "Resignation",
+            EndReason::Timeout => "Timeout",
+        }
+    }
+
+    /// The text of the quit message which tells the remote why the game ended. chesstp only
+    /// carries the winner in move messages, so this is how resignations and timeouts are sent.
+    pub fn quit_message(&self) -> &'static str {
+        self.title()
+    }
+
+    /// Inverse of `quit_message`. Returns `None` for quit messages which don't name a reason.
+    pub fn from_quit_message(message: &str) -> Option<Self> {
+        [
+            EndReason::Checkmate,
+            EndReason::Draw(DrawReason::Agreement),
+            EndReason::Resignation,
+            EndReason::Timeout,
+        ]
+        .into_iter()
+        .find(|reason| reason.quit_message().eq_ignore_ascii_case(message.trim()))
+    }
+}
+
+/// The result of a finished game, for embedders which need it as a value rather than UI text.
+///
+/// Is part of the stable library API: existing fields and their meaning won't change, though
+/// `EndReason` and `DrawReason` may gain variants.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct GameOutcome {
+    /// `None` if the game was drawn.
+    pub winner: Option<Color>,
+    pub reason: EndReason,
+}
+
+/// Something which happened in a game, sent to the subscriber registered with
+/// `GameState::subscribe` as it happens.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameEvent {
+    /// The local player played a move.
+    MoveMade {
+        source: Position,
+        dest: Position,
+        outcome: MoveOutcome,
+    },
+    /// The remote played a move, which has been applied to the board.
+    MoveReceived { source: Position, dest: Position },
+    GameEnded {
+        phase: chesstp::GamePhase,
+        reason: EndReason,
+    },
+    /// The connection to the remote stopped working. Is only sent once per game.
+    ConnectionLost(network::ConnectionHealth),
 }
 
+/// Why a move couldn't be played.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum MoveError {
     BadCoordinates,
+    /// The source square doesn't hold a piece of the side to move.
     WrongPlayer,
+    /// The piece can't move to the destination.
     Invalid,
+    /// The move would leave the mover's own king in check.
     Checked,
+    /// The game has already ended.
+    GameOver,
 }
 
 /// Wrapper around `chess::chess::game::game_state`.
@@ -420,83 +1028,400 @@ impl BoardWrapper {
         Self(game_state)
     }
 
+    /// Returns the piece on `position`. A square the library describes with an unknown piece or
+    /// player is treated as empty, see `try_at`. It isn't logged here, since this is called for
+    /// every square every frame, but when the board changes, see `log_unknown_squares`.
     pub fn at(&self, position: Position) -> Option<Piece> {
+        self.try_at(position).unwrap_or(None)
+    }
+
+    /// Every square the library describes with an unknown piece or player, see `try_at`.
+    pub fn unknown_squares(&self) -> Vec<(Position, chesstp::TileError)> {
+        Position::all()
+            .filter_map(|position| self.try_at(position).err().map(|error| (position, error)))
+            .collect()
+    }
+
+    /// Log every square which `at` treats as empty because the library describes it with an
+    /// unknown piece or player. Called whenever the board is loaded or changed by a move.
+    pub fn log_unknown_squares(&self) {
+        for (position, error) in self.unknown_squares() {
+            println!("Warning: treating {:?} as empty, {}", position, error);
+        }
+    }
+
+    /// Like `at`, but returns an error if the library describes the square with a piece or player
+    /// which isn't known.
+    pub fn try_at(&self, position: Position) -> Result<Option<Piece>, chesstp::TileError> {
         if self
             .0
             .empty(position.row() as i32 + 1, position.column() as i32 + 1)
         {
-            return None;
+            return Ok(None);
         }
 
         let piece_str = self
             .0
             .get_piece(position.row() as i32 + 1, position.column() as i32 + 1);
-        let color_str = self
+        let color_char = self
             .0
             .get_player(position.row() as i32 + 1, position.column() as i32 + 1);
 
-        Some(Piece {
-            kind: match piece_str.as_str() {
-                "pawn" => PieceKind::Pawn,
-                "knight" => PieceKind::Knight,
-                "bishop" => PieceKind::Bishop,
-                "rook" => PieceKind::Rook,
-                "king" => PieceKind::King,
-                "queen" => PieceKind::Queen,
-                _ => unreachable!(),
-            },
-            color: match color_str {
-                'w' => Color::White,
-                'b' => Color::Black,
-                _ => unreachable!(),
-            },
-        })
+        Ok(Some(Piece {
+            kind: PieceKind::from_library_name(&piece_str)
+                .ok_or_else(|| chesstp::TileError::InvalidPiece(piece_str.clone()))?,
+            color: Color::from_fen_char(color_char)
+                .ok_or(chesstp::TileError::InvalidPlayer(color_char))?,
+        }))
     }
 
-    pub fn turn(&self) -> Color {
-        match self.0.turn {
-            'w' => Color::White,
-            'b' => Color::Black,
-            _ => unreachable!(),
-        }
+    /// Like `at`, but with the square given in algebraic notation, e.g. "e4". Returns `None` if the
+    /// square can't be parsed.
+    pub fn at_str(&self, square: &str) -> Option<Piece> {
+        self.at(Position::parse(square)?)
+    }
+
+    pub fn turn(&self) -> Color {
+        Color::from_fen_char(self.0.turn).expect("the library only uses 'w' and 'b'")
     }
     pub fn set_turn(&mut self, player: Color) {
-        self.0.turn = match player {
-            Color::White => 'w',
-            Color::Black => 'b',
-        }
+        self.0.turn = player.to_fen_char();
     }
 
-    pub fn valid_moves(&mut self, square: Position) -> impl Iterator<Item = Position> {
-        self.0
+    /// Returns the squares the piece on `square` can legally move to. The library needs mutable
+    /// access to compute them, so the query is made on a copy of the game.
+    pub fn valid_moves(&self, square: Position) -> impl Iterator<Item = Position> {
+        let mut game = self.0.clone();
+        let moves: Vec<_> = game
             .valid_moves(square.row() as i32 + 1, square.column() as i32 + 1)
             .into_iter()
             .map(|(row, column)| {
                 Position::new(column as u8 - 1, row as u8 - 1)
                     .expect("library returns valid positions")
             })
+            .collect();
+
+        moves.into_iter()
+    }
+
+    /// Whether `color` has any legal move, assuming it's their turn.
+    pub fn has_legal_moves(&self, color: Color) -> bool {
+        Position::all()
+            .filter(|&square| self.at(square).is_some_and(|piece| piece.color == color))
+            .any(|square| self.valid_moves(square).next().is_some())
+    }
+
+    /// The summed value of the pieces `color` has left.
+    pub fn material(&self, color: Color, values: &PieceValues) -> f32 {
+        Position::all()
+            .filter_map(|square| self.at(square))
+            .filter(|piece| piece.color == color)
+            .map(|piece| values.value(piece.kind))
+            .sum()
+    }
+
+    /// Whether neither side can possibly checkmate, i.e. only kings remain besides a single knight,
+    /// or bishops which all stand on squares of the same color.
+    pub fn has_insufficient_material(&self) -> bool {
+        let mut knights = 0;
+        let mut bishop_square_colors = Vec::new();
+        for square in Position::all() {
+            match self.at(square).map(|piece| piece.kind) {
+                None | Some(PieceKind::King) => {}
+                Some(PieceKind::Knight) => knights += 1,
+                Some(PieceKind::Bishop) => {
+                    bishop_square_colors.push((square.column() + square.row()) % 2)
+                }
+                Some(_) => return false,
+            }
+        }
+
+        match (knights, bishop_square_colors.as_slice()) {
+            (0 | 1, []) => true,
+            (0, [first, rest @ ..]) => rest.iter().all(|color| color == first),
+            _ => false,
+        }
+    }
+
+    /// Whether moving from `source` to `dest` captures or moves a pawn, which resets the
+    /// fifty-move rule and makes earlier positions impossible to repeat. Must be called before the
+    /// move is played.
+    pub fn is_irreversible(&self, source: Position, dest: Position) -> bool {
+        self.at(source)
+            .is_some_and(|piece| piece.kind == PieceKind::Pawn)
+            || self.at(dest).is_some()
     }
 
+    /// Whether moving from `source` to `dest` may take away a castling right, by moving a king or
+    /// rook from its starting square or capturing a rook on one. Positions from before such a move
+    /// don't count as repeated after it. A king or rook which has already returned to its starting
+    /// square counts too, which only misses some repetitions. Must be called before the move is
+    /// played.
+    pub fn may_change_castling_rights(&self, source: Position, dest: Position) -> bool {
+        let is_castling_piece = |square: Position| {
+            let home_row = match self.at(square).map(|piece| piece.color) {
+                Some(Color::White) => 0,
+                Some(Color::Black) => 7,
+                None => return false,
+            };
+            let home_columns: &[u8] = match self.at(square).map(|piece| piece.kind) {
+                Some(PieceKind::King) => &[4],
+                Some(PieceKind::Rook) => &[0, 7],
+                _ => &[],
+            };
+            square.row() == home_row && home_columns.contains(&square.column())
+        };
+
+        is_castling_piece(source) || is_castling_piece(dest)
+    }
+
+    /// Identifies the position for detecting repetitions, by the piece placement and the side to
+    /// move.
+    fn position_key(&self) -> String {
+        format!(
+            "{} {}",
+            chesstp::Board::from(self.0.clone()).serialize(),
+            self.turn().to_fen_char()
+        )
+    }
+
+    /// Every legal move of `color` as source and destination squares, assuming it's their turn.
+    /// Simulates a move per piece, so call it once per move rather than every frame.
+    pub fn all_legal_moves(&self, color: Color) -> Vec<(Position, Position)> {
+        Position::all()
+            .filter(|&square| self.at(square).is_some_and(|piece| piece.color == color))
+            .flat_map(|source| self.valid_moves(source).map(move |dest| (source, dest)))
+            .collect()
+    }
+
+    /// Count the positions reached after exactly `depth` half moves from this one, playing every
+    /// legal move of the side to move at each step. Comparing against published numbers validates
+    /// move generation. Promotions only count once, since the library picks the promoted piece.
+    pub fn perft(&self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        self.all_legal_moves(self.turn())
+            .into_iter()
+            .map(|(source, dest)| {
+                let mut board = Self(self.0.clone());
+                match board.make_move(source, dest, None) {
+                    Ok(_) => board.perft(depth - 1),
+                    Err(error) => {
+                        println!(
+                            "Legal move {:?}{:?} was rejected: {:?}",
+                            source, dest, error
+                        );
+                        0
+                    }
+                }
+            })
+            .sum()
+    }
+
+    /// Like `valid_moves`, but with the square given in algebraic notation, e.g. "e2". Returns no
+    /// moves if the square can't be parsed.
+    pub fn valid_moves_str(&self, square: &str) -> impl Iterator<Item = Position> {
+        Position::parse(square)
+            .into_iter()
+            .flat_map(|square| self.valid_moves(square))
+    }
+
+    /// Play `source` to `dest` for the side to move. A pawn reaching the last rank is promoted to
+    /// `promotion`, or to whatever the library picks if it's `None`.
     pub fn make_move(
         &mut self,
         source: Position,
         dest: Position,
+        promotion: Option<PieceKind>,
     ) -> Result<MoveOutcome, MoveError> {
         use chess::outcome::Outcome;
-        match self.0.make_move(
+        let promoted = promotion
+            .filter(|_| self.is_promotion(source, dest))
+            .map(|kind| Piece {
+                kind,
+                color: self.turn(),
+            });
+
+        let outcome = match self.0.make_move(
             source.row() as i32 + 1,
             source.column() as i32 + 1,
             dest.row() as i32 + 1,
             dest.column() as i32 + 1,
         ) {
-            Outcome::Valid => Ok(MoveOutcome::Valid),
-            Outcome::Check => Ok(MoveOutcome::Check),
-            Outcome::Checkmate => Ok(MoveOutcome::Checkmate),
-            Outcome::Bad_coordinates => Err(MoveError::BadCoordinates),
-            Outcome::Wrong_player => Err(MoveError::WrongPlayer),
-            Outcome::Invalid => Err(MoveError::Invalid),
-            Outcome::Checked => Err(MoveError::Checked),
+            Outcome::Valid => MoveOutcome::Valid,
+            Outcome::Check => MoveOutcome::Check,
+            Outcome::Checkmate => MoveOutcome::Checkmate,
+            Outcome::Bad_coordinates => return Err(MoveError::BadCoordinates),
+            Outcome::Wrong_player => return Err(MoveError::WrongPlayer),
+            Outcome::Invalid => return Err(MoveError::Invalid),
+            Outcome::Checked => return Err(MoveError::Checked),
+        };
+        let Some(piece) = promoted else {
+            return Ok(outcome);
+        };
+
+        // The library judged the position with the piece it promoted to, so judge it again.
+        self.set_piece(dest, Some(piece));
+        let opponent = piece.color.opposite();
+        Ok(
+            match (self.is_in_check(opponent), self.has_legal_moves(opponent)) {
+                (true, false) => MoveOutcome::Checkmate,
+                (true, true) => MoveOutcome::Check,
+                (false, _) => MoveOutcome::Valid,
+            },
+        )
+    }
+
+    /// Check that `mover` playing `source` to `dest` (promoting to `promotion`) on this board
+    /// results in the `received` board. Returns a description of the mismatch otherwise.
+    pub fn verify_move(
+        &self,
+        source: Position,
+        dest: Position,
+        promotion: Option<PieceKind>,
+        mover: Color,
+        received: &chesstp::Board,
+    ) -> Result<(), String> {
+        let mut simulated = BoardWrapper::new(self.0.clone());
+        simulated.set_turn(mover);
+        if let Err(error) = simulated.make_move(source, dest, promotion) {
+            return Err(format!(
+                "move {:?}{:?} is invalid on the local board: {:?}",
+                source, dest, error
+            ));
         }
+
+        let expected: chesstp::Board = simulated.0.into();
+        let expected_str = expected.serialize();
+        let received_str = received.serialize();
+        if expected_str != received_str {
+            return Err(format!(
+                "expected board {} but received {}",
+                expected_str, received_str
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Replace the piece on `position`, e.g. to promote to another piece than the library picks.
+    pub fn set_piece(&mut self, position: Position, piece: Option<Piece>) {
+        let mut board: chesstp::Board = self.0.clone().into();
+        board
+            .set_tile(position, piece)
+            .expect("boards of the library contain every tile");
+        board.update_game(&mut self.0);
+    }
+
+    /// Whether moving `source` to `dest` would promote a pawn, i.e. a pawn moving to the first or
+    /// last rank. Doesn't check that the move is legal.
+    pub fn is_promotion(&self, source: Position, dest: Position) -> bool {
+        self.at(source)
+            .is_some_and(|piece| piece.kind == PieceKind::Pawn)
+            && (dest.row() == 0 || dest.row() == 7)
+    }
+
+    /// Whether moving `source` to `dest` would be an en passant capture, i.e. a pawn moving
+    /// diagonally to an empty square. Doesn't check that the move is legal.
+    pub fn is_en_passant(&self, source: Position, dest: Position) -> bool {
+        self.at(source)
+            .is_some_and(|piece| piece.kind == PieceKind::Pawn)
+            && source.column() != dest.column()
+            && self.at(dest).is_none()
+    }
+
+    /// Whether moving `source` to `dest` puts the opponent of the side to move in check.
+    pub fn gives_check(&self, source: Position, dest: Position) -> bool {
+        let mut simulated = BoardWrapper::new(self.0.clone());
+        matches!(
+            simulated.make_move(source, dest, None),
+            Ok(MoveOutcome::Check | MoveOutcome::Checkmate)
+        )
+    }
+
+    /// Returns the square of `color`'s king, or `None` if there is none, e.g. on an edited board.
+    pub fn king_position(&self, color: Color) -> Option<Position> {
+        Position::all().find(|&square| {
+            self.at(square)
+                == Some(Piece {
+                    kind: PieceKind::King,
+                    color,
+                })
+        })
+    }
+
+    /// Whether `color`'s king is attacked, regardless of whose turn it is or how the position came
+    /// about. Unlike `MoveOutcome::Check`, this also works for received or loaded positions.
+    pub fn is_in_check(&self, color: Color) -> bool {
+        self.king_position(color)
+            .is_some_and(|king| self.attacked_squares(color.opposite()).contains(&king))
+    }
+
+    /// Returns the square of the side to move's king if it's in check.
+    pub fn checked_king(&self) -> Option<Position> {
+        let side = self.turn();
+        self.king_position(side)
+            .filter(|king| self.attacked_squares(side.opposite()).contains(king))
+    }
+
+    /// Returns the squares attacked by the pieces of `color`, i.e. the squares they could capture
+    /// on if an opposing piece stood there. Squares occupied by `color`'s own pieces count as
+    /// attacked (defended), and whether a capture would expose the king is ignored.
+    ///
+    /// This is computed from the piece placement directly rather than with the library's
+    /// `valid_moves`, since that only generates legal moves, which excludes defended squares and
+    /// includes pawn pushes which aren't attacks.
+    pub fn attacked_squares(&self, color: Color) -> HashSet<Position> {
+        let mut attacked = HashSet::new();
+
+        for square in Position::all() {
+            let Some(piece) = self.at(square).filter(|piece| piece.color == color) else {
+                continue;
+            };
+
+            let (offsets, directions): (&[(i8, i8)], &[(i8, i8)]) = match piece.kind {
+                PieceKind::Pawn => {
+                    let forward = match color {
+                        Color::White => 1,
+                        Color::Black => -1,
+                    };
+                    attacked.extend(
+                        [(-1, forward), (1, forward)]
+                            .into_iter()
+                            .filter_map(|offset| square.translated(offset)),
+                    );
+                    continue;
+                }
+                PieceKind::Knight => (&KNIGHT_OFFSETS[..], &[][..]),
+                PieceKind::King => (&KING_OFFSETS[..], &[][..]),
+                PieceKind::Bishop => (&[][..], &BISHOP_DIRECTIONS[..]),
+                PieceKind::Rook => (&[][..], &ROOK_DIRECTIONS[..]),
+                // A queen moves in the same directions as a king, only further.
+                PieceKind::Queen => (&[][..], &KING_OFFSETS[..]),
+            };
+
+            attacked.extend(
+                offsets
+                    .iter()
+                    .filter_map(|&offset| square.translated(offset)),
+            );
+
+            for &direction in directions {
+                let mut current = square;
+                while let Some(next) = current.translated(direction) {
+                    attacked.insert(next);
+                    if self.at(next).is_some() {
+                        // The ray is blocked.
+                        break;
+                    }
+                    current = next;
+                }
+            }
+        }
+
+        attacked
     }
 
     pub fn inner(&self) -> &chess::game::game_state {
@@ -515,14 +1440,119 @@ enum TurnPhase {
     SelectDest(Position),
     /// Waiting for remote player to perform their move. Only valid if connected to a remote.
     WaitingForRemote,
+    /// A move has been picked but is only played once its destination is selected again. Only
+    /// used if `GameState::confirm_moves` is set.
+    ConfirmMove {
+        source: Position,
+        dest: Position,
+    },
+    /// A pawn move to the last rank has been picked, and is played once the piece to promote to
+    /// has been chosen. Only used if `GameState::auto_promote` isn't set.
+    ChoosePromotion {
+        source: Position,
+        dest: Position,
+    },
 }
 
-impl TurnPhase {
-    pub fn source_square(&self) -> Option<Position> {
-        match self {
-            Self::SelectDest(square) => Some(*square),
-            _ => None,
+/// Returns the banner text shown when the remote left, including the reason they gave unless it
+/// was empty or just named why the game ended.
+pub fn opponent_left_text(quit_message: Option<&str>) -> String {
+    match quit_message.map(str::trim) {
+        Some(message) if !message.is_empty() && EndReason::from_quit_message(message).is_none() => {
+            format!("Opponent left: {}", message)
         }
+        _ => "Opponent left".to_owned(),
+    }
+}
+
+/// Returns `text` unchanged if `width_of` it is at most `max_width`, or else the longest start of
+/// it which fits when followed by an ellipsis.
+pub fn elide_to_fit(
+    text: &str,
+    max_width: f32,
+    mut width_of: impl FnMut(&str) -> GameResult<f32>,
+) -> GameResult<String> {
+    if width_of(text)? <= max_width {
+        return Ok(text.to_owned());
+    }
+
+    for (end, _) in text.char_indices().rev() {
+        let elided = format!("{}…", text[..end].trim_end());
+        if width_of(&elided)? <= max_width {
+            return Ok(elided);
+        }
+    }
+    Ok("…".to_owned())
+}
+
+/// Number of dots shown by the waiting indicator `elapsed` into its animation, cycling from 0 to 3.
+pub fn waiting_dot_count(elapsed: Duration) -> usize {
+    (elapsed.as_millis() / WAITING_DOT_INTERVAL.as_millis()) as usize % 4
+}
+
+/// Opacity of the turn indicator flash `elapsed` after it started, fading linearly from 1 to 0
+/// over `TURN_FLASH_DURATION`.
+pub fn turn_flash_alpha(elapsed: Duration) -> f32 {
+    1.0 - (elapsed.as_secs_f32() / TURN_FLASH_DURATION.as_secs_f32()).min(1.0)
+}
+
+/// How far the board has turned `elapsed` after it started flipping, easing in and out from 0 to
+/// 1 over `FLIP_DURATION`.
+pub fn flip_progress(elapsed: Duration) -> f32 {
+    let linear = (elapsed.as_secs_f32() / FLIP_DURATION.as_secs_f32()).min(1.0);
+    linear * linear * (3.0 - 2.0 * linear)
+}
+
+/// Returns where a square with the bounds `final_bounds` is drawn while the board turns to its new
+/// orientation, `progress` of the way there. The square moves around the center of
+/// `board_bounds`, starting half a turn away where the old orientation placed it. Stays upright,
+/// so that pieces are never drawn upside down.
+fn flipping_square_bounds(
+    board_bounds: graphics::Rect,
+    final_bounds: graphics::Rect,
+    progress: f32,
+) -> graphics::Rect {
+    let board_center: glam::Vec2 = board_bounds.center().into();
+    let final_center: glam::Vec2 = final_bounds.center().into();
+    let angle = std::f32::consts::PI * (1.0 - progress);
+    let center = board_center + glam::Vec2::from_angle(angle).rotate(final_center - board_center);
+
+    graphics::Rect {
+        x: center.x - final_bounds.w / 2.0,
+        y: center.y - final_bounds.h / 2.0,
+        ..final_bounds
+    }
+}
+
+/// A half move which has been played.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MoveRecord {
+    pub source: Position,
+    pub dest: Position,
+    /// How long the mover took, measured from when it became their turn.
+    pub duration: Duration,
+}
+
+/// The state of a local game before a half move, which `GameState::undo_move` restores.
+struct Snapshot {
+    board: chess::game::game_state,
+    last_move: Option<(Position, Position)>,
+    game_phase: chesstp::GamePhase,
+    end_reason: Option<EndReason>,
+    halfmove_clock: u32,
+    position_counts: HashMap<String, u32>,
+    draw_offered_by: Option<Color>,
+}
+
+/// Format how long a move took, e.g. "3.2s" or "1m 05s".
+pub fn format_move_duration(duration: Duration) -> String {
+    // Round before picking the format, so that e.g. 59.97 seconds reads "1m 00s" and not "60.0s".
+    let tenths = (duration.as_millis() + 50) / 100;
+    if tenths < 600 {
+        format!("{}.{}s", tenths / 10, tenths % 10)
+    } else {
+        let seconds = tenths / 10;
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
     }
 }
 
@@ -532,45 +1562,312 @@ struct GameState {
     /// as the source.
     /// Information about the last half move. Contains the source and destination squares.
     pub last_move: Option<(Position, Position)>,
+    /// Every half move played so far, in order.
+    pub moves: Vec<MoveRecord>,
+    /// When the side to move got their turn.
+    pub turn_started_at: Instant,
+    /// Half moves since the last capture or pawn move, for the fifty-move rule.
+    pub halfmove_clock: u32,
+    /// How many times each position has occurred since the last capture or pawn move, keyed by
+    /// `BoardWrapper::position_key`.
+    pub position_counts: HashMap<String, u32>,
+    /// When a received move last made it the local player's turn, which flashes the turn
+    /// indicator. Is `None` until the first received move, so the first turn never flashes.
+    pub turn_flash_started_at: Option<Instant>,
     pub turn_phase: TurnPhase,
     pub game_phase: chesstp::GamePhase,
+    /// Why the game ended. Is `None` while `game_phase` is ongoing.
+    pub end_reason: Option<EndReason>,
+    /// The reason the remote gave when leaving, if they have left.
+    pub remote_quit_message: Option<String>,
     pub connection: network::GameConnection,
+    /// The color played on this machine. `None` in local games, where both colors are.
+    pub my_color: Option<Color>,
+    pub orientation: Orientation,
+    /// Whether the board turns to face the side to move after every move, for hot-seat games where
+    /// both players share the screen.
+    pub auto_flip: bool,
+    /// Whether to check that received moves result in the received board.
+    pub verify_remote_moves: bool,
+    /// Set once a received board didn't match the received move played on the local board.
+    pub desync_detected: bool,
+    /// A warning about something which just happened, and when it happened. Is shown for
+    /// `TRANSIENT_WARNING_DURATION`.
+    pub transient_warning: Option<(String, Instant)>,
+    /// The side which offered a draw in a local game, until the opponent accepts it or declines it
+    /// by moving.
+    pub draw_offered_by: Option<Color>,
+    /// When the side to move asked to resign in a local game, which they have to confirm within
+    /// `TRANSIENT_WARNING_DURATION`.
+    pub resignation_requested_at: Option<Instant>,
+    /// Where the selected piece can move, cached when it's selected. Empty when no piece is
+    /// selected.
+    pub potential_dests: HashSet<Position>,
+    /// The subset of `potential_dests` which would give check. Only computed if
+    /// `highlight_checking_moves` is set, since it simulates every potential move.
+    pub checking_dests: HashSet<Position>,
+    pub highlight_checking_moves: bool,
+    /// Whether moves have to be confirmed by selecting the destination again before they're
+    /// played, see `TurnPhase::ConfirmMove`.
+    pub confirm_moves: bool,
+    /// What pawns moved to the last rank by selecting squares are promoted to. `None` lets the
+    /// player choose, see `TurnPhase::ChoosePromotion`.
+    pub auto_promote: Option<PieceKind>,
+    /// Squares controlled by the opponent of the currently hovered king, which are tinted as a
+    /// learning aid. Empty when no king is hovered.
+    pub threatened_squares: HashSet<Position>,
+    /// The square of the side to move's king if it's in check, which is highlighted. Is updated
+    /// whenever the board changes, see `BoardWrapper::is_in_check`.
+    pub checked_king: Option<Position>,
+    /// The state before each half move played in a local game, most recent last, for taking moves
+    /// back. Is always empty in networked games.
+    undo_stack: Vec<Snapshot>,
+    /// Where events are sent, if anyone has subscribed.
+    event_sender: Option<mpsc::Sender<GameEvent>>,
+    /// Whether `GameEvent::ConnectionLost` has been sent.
+    connection_lost_reported: bool,
 }
 
 impl GameState {
     pub fn new(board: BoardWrapper, connection: network::GameConnection) -> Self {
+        // The server always plays black.
+        let my_color = match connection {
+            network::GameConnection::Local | network::GameConnection::Analysis => None,
+            network::GameConnection::Remote(network::ConnectionType::Server, _, _) => {
+                Some(Color::Black)
+            }
+            network::GameConnection::Remote(network::ConnectionType::Client, _, _) => {
+                Some(Color::White)
+            }
+        };
+
+        board.log_unknown_squares();
+        let position_counts = HashMap::from([(board.position_key(), 1)]);
+        let checked_king = board.checked_king();
+
         Self {
+            turn_phase: if my_color.is_some_and(|color| color != board.turn()) {
+                TurnPhase::WaitingForRemote
+            } else {
+                TurnPhase::SelectSource
+            },
             board,
             last_move: None,
-            turn_phase: match connection {
-                network::GameConnection::Remote(network::ConnectionType::Server, _, _) => {
-                    TurnPhase::WaitingForRemote
-                }
-                _ => TurnPhase::SelectSource,
-            },
+            moves: Vec::new(),
+            turn_started_at: Instant::now(),
+            halfmove_clock: 0,
+            position_counts,
+            turn_flash_started_at: None,
             game_phase: chesstp::GamePhase::Ongoing,
+            end_reason: None,
+            remote_quit_message: None,
             connection,
+            my_color,
+            orientation: Orientation::facing(my_color.unwrap_or(Color::White)),
+            auto_flip: false,
+            verify_remote_moves: true,
+            desync_detected: false,
+            transient_warning: None,
+            draw_offered_by: None,
+            resignation_requested_at: None,
+            potential_dests: HashSet::new(),
+            checking_dests: HashSet::new(),
+            highlight_checking_moves: true,
+            confirm_moves: false,
+            auto_promote: None,
+            threatened_squares: HashSet::new(),
+            checked_king,
+            undo_stack: Vec::new(),
+            event_sender: None,
+            connection_lost_reported: false,
+        }
+    }
+
+    /// Start sending events to the returned receiver, replacing any previous subscriber. Events
+    /// are only constructed while someone is subscribed.
+    pub fn subscribe(&mut self) -> mpsc::Receiver<GameEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.event_sender = Some(sender);
+        receiver
+    }
+
+    fn emit(&mut self, event: GameEvent) {
+        if let Some(sender) = &self.event_sender {
+            if sender.send(event).is_err() {
+                // The receiver was dropped, so stop sending.
+                self.event_sender = None;
+            }
+        }
+    }
+
+    /// End the game, unless it has already ended.
+    fn end(&mut self, phase: chesstp::GamePhase, reason: EndReason) {
+        if self.game_phase == chesstp::GamePhase::Ongoing {
+            self.game_phase = phase;
+            self.end_reason = Some(reason);
+            self.emit(GameEvent::GameEnded { phase, reason });
+        }
+    }
+
+    /// Returns who won the game and why, or `None` while it's ongoing.
+    pub fn result(&self) -> Option<GameOutcome> {
+        let reason = self.end_reason?;
+        match self.game_phase {
+            chesstp::GamePhase::Ongoing => None,
+            chesstp::GamePhase::Win(winner) => Some(GameOutcome {
+                winner: Some(winner),
+                reason,
+            }),
+            chesstp::GamePhase::Draw => Some(GameOutcome {
+                winner: None,
+                reason,
+            }),
+        }
+    }
+
+    /// Update the counters behind the repetition and fifty-move rules after a move has been played,
+    /// returning the draw rule which now ends the game without a claim, if any. `irreversible` is
+    /// whether the move captured or moved a pawn, see `BoardWrapper::is_irreversible`, and
+    /// `castling_changed` whether it may have taken away a castling right, see
+    /// `BoardWrapper::may_change_castling_rights`.
+    ///
+    /// Repetitions and move counts aren't tracked in analysis, where either color may move at any
+    /// time.
+    fn track_draw_rules(
+        &mut self,
+        irreversible: bool,
+        castling_changed: bool,
+    ) -> Option<DrawReason> {
+        if matches!(self.connection, network::GameConnection::Analysis) {
+            return self
+                .board
+                .has_insufficient_material()
+                .then_some(DrawReason::InsufficientMaterial);
+        }
+
+        if irreversible {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if irreversible || castling_changed {
+            self.position_counts.clear();
+        }
+        let count = self
+            .position_counts
+            .entry(self.board.position_key())
+            .or_insert(0);
+        *count += 1;
+
+        if *count >= AUTOMATIC_REPETITIONS {
+            Some(DrawReason::Repetition)
+        } else if self.halfmove_clock >= AUTOMATIC_MOVE_LIMIT {
+            Some(DrawReason::FiftyMove)
+        } else if self.board.has_insufficient_material() {
+            Some(DrawReason::InsufficientMaterial)
+        } else {
+            None
         }
     }
 
+    /// The draw rule which the side to move may claim a draw by, if any. Claims aren't possible in
+    /// analysis, or once the game is over.
+    pub fn claimable_draw(&self) -> Option<DrawReason> {
+        if self.game_phase != chesstp::GamePhase::Ongoing
+            || matches!(self.connection, network::GameConnection::Analysis)
+        {
+            return None;
+        }
+
+        let repetitions = self
+            .position_counts
+            .get(&self.board.position_key())
+            .copied()
+            .unwrap_or(0);
+        if repetitions >= CLAIMABLE_REPETITIONS {
+            Some(DrawReason::Repetition)
+        } else if self.halfmove_clock >= CLAIMABLE_MOVE_LIMIT {
+            Some(DrawReason::FiftyMove)
+        } else {
+            None
+        }
+    }
+
+    /// Store a half move which has just been played, and start timing the next one.
+    fn record_move(&mut self, source: Position, dest: Position) {
+        let now = Instant::now();
+        self.last_move = Some((source, dest));
+        self.moves.push(MoveRecord {
+            source,
+            dest,
+            duration: now - self.turn_started_at,
+        });
+        self.turn_started_at = now;
+        self.checked_king = self.board.checked_king();
+        self.board.log_unknown_squares();
+    }
+
+    /// Select the piece on `source` to be moved, caching where it can move.
+    fn select_source(&mut self, source: Position) {
+        self.turn_phase = TurnPhase::SelectDest(source);
+        self.potential_dests = self.board.valid_moves(source).collect();
+        self.checking_dests = if self.highlight_checking_moves {
+            self.potential_dests
+                .iter()
+                .copied()
+                .filter(|&dest| self.board.gives_check(source, dest))
+                .collect()
+        } else {
+            HashSet::new()
+        };
+    }
+
+    /// Whether the local player may move right now: the game is ongoing, no remote move is awaited
+    /// and the side to move is played on this machine.
+    pub fn is_my_turn(&self) -> bool {
+        self.game_phase == chesstp::GamePhase::Ongoing
+            && self.turn_phase != TurnPhase::WaitingForRemote
+            && self.my_color.is_none_or(|color| color == self.board.turn())
+    }
+
     pub fn select_square(&mut self, square: Position) {
-        if self.game_phase != chesstp::GamePhase::Ongoing {
+        if !self.is_my_turn() {
             return;
         }
         match self.turn_phase {
             TurnPhase::SelectSource => {
-                if self
-                    .board
-                    .at(square)
-                    .is_none_or(|piece| piece.color != self.board.turn())
-                {
+                let Some(piece) = self.board.at(square) else {
+                    return;
+                };
+                if matches!(self.connection, network::GameConnection::Analysis) {
+                    // Let whichever color was picked move next.
+                    self.board.set_turn(piece.color);
+                } else if piece.color != self.board.turn() {
                     return;
                 }
 
-                self.turn_phase = TurnPhase::SelectDest(square);
+                self.select_source(square);
             }
             TurnPhase::SelectDest(source) => {
-                match self.board.make_move(source, square) {
+                let is_potential_dest = self.potential_dests.contains(&square);
+                self.potential_dests.clear();
+                self.checking_dests.clear();
+                if is_potential_dest && self.confirm_moves {
+                    self.turn_phase = TurnPhase::ConfirmMove {
+                        source,
+                        dest: square,
+                    };
+                    return;
+                }
+                if is_potential_dest && self.asks_for_promotion(source, square) {
+                    self.turn_phase = TurnPhase::ChoosePromotion {
+                        source,
+                        dest: square,
+                    };
+                    return;
+                }
+
+                match self.try_move(source, square, self.auto_promote) {
                     Err(error) => {
                         // Interrpret as canceling the move.
                         println!("Invalid move: {:?}", error);
@@ -579,64 +1876,484 @@ impl GameState {
                     }
                     Ok(outcome) => {
                         dbg!(outcome);
-
-                        if outcome == MoveOutcome::Checkmate {
-                            self.game_phase = chesstp::GamePhase::Win(self.board.turn().opposite());
-                        }
-
-                        self.last_move = Some((source, square));
-                        self.turn_phase = match self.connection {
-                            network::GameConnection::Local => TurnPhase::SelectSource,
-                            network::GameConnection::Remote(_, _, ref mut stream) => {
-                                let move_message = chesstp::MoveMessage {
-                                    source,
-                                    dest: square,
-                                    // My game doesn't support promotion. :)
-                                    promotion: None,
-                                    phase: self.game_phase,
-                                    board: self.board.inner().clone().into(),
-                                };
-
-                                stream.write(chesstp::Message::Move(move_message)).unwrap();
-                                TurnPhase::WaitingForRemote
-                            }
-                        };
                     }
                 }
             }
+            TurnPhase::ConfirmMove { source, dest }
+                if square == dest && self.asks_for_promotion(source, dest) =>
+            {
+                self.turn_phase = TurnPhase::ChoosePromotion { source, dest };
+            }
+            TurnPhase::ConfirmMove { source, dest } if square == dest => {
+                if let Err(error) = self.try_move(source, dest, self.auto_promote) {
+                    println!("Invalid move: {:?}", error);
+                    self.turn_phase = TurnPhase::SelectSource;
+                }
+            }
+            TurnPhase::ConfirmMove { .. } => {
+                // Selecting any other square cancels the staged move.
+                self.cancel_staged_move();
+            }
+            // The board is blocked until a piece has been chosen or the move is canceled.
+            TurnPhase::ChoosePromotion { .. } => {}
             TurnPhase::WaitingForRemote => {}
         }
     }
 
+    /// Whether moving `source` to `dest` by selecting squares has to wait for the player to choose
+    /// what to promote to.
+    fn asks_for_promotion(&self, source: Position, dest: Position) -> bool {
+        self.auto_promote.is_none() && self.board.is_promotion(source, dest)
+    }
+
+    /// Play the move waiting for a promotion choice, promoting to `kind`. Returns whether a move
+    /// was waiting.
+    pub fn choose_promotion(&mut self, kind: PieceKind) -> bool {
+        let TurnPhase::ChoosePromotion { source, dest } = self.turn_phase else {
+            return false;
+        };
+
+        if let Err(error) = self.try_move(source, dest, Some(kind)) {
+            println!("Invalid move: {:?}", error);
+            self.turn_phase = TurnPhase::SelectSource;
+        }
+        true
+    }
+
+    /// Discard the move waiting for confirmation or a promotion choice, if there is one. Returns
+    /// whether there was.
+    pub fn cancel_staged_move(&mut self) -> bool {
+        if let TurnPhase::ConfirmMove { .. } | TurnPhase::ChoosePromotion { .. } = self.turn_phase {
+            self.turn_phase = TurnPhase::SelectSource;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Take back the last half move of a local game, restoring the position and game state from
+    /// before it, even if it ended the game. Can be repeated to take back several moves. Returns
+    /// whether there was a move to take back.
+    pub fn undo_move(&mut self) -> bool {
+        let Some(snapshot) = self.undo_stack.pop() else {
+            return false;
+        };
+
+        *self.board.inner_mut() = snapshot.board;
+        self.last_move = snapshot.last_move;
+        self.game_phase = snapshot.game_phase;
+        self.end_reason = snapshot.end_reason;
+        self.halfmove_clock = snapshot.halfmove_clock;
+        self.position_counts = snapshot.position_counts;
+        self.draw_offered_by = snapshot.draw_offered_by;
+        self.moves.pop();
+        self.turn_started_at = Instant::now();
+        self.turn_phase = TurnPhase::SelectSource;
+        self.potential_dests.clear();
+        self.checking_dests.clear();
+        self.checked_king = self.board.checked_king();
+        true
+    }
+
+    /// Offer a draw on behalf of the side to move, or accept the opponent's offer. Claims the draw
+    /// instead if a rule makes it claimable, see `claimable_draw`. Only works in ongoing local
+    /// games, since chesstp can't carry draw offers. Returns whether anything happened.
+    pub fn offer_or_accept_draw(&mut self) -> bool {
+        if self.game_phase != chesstp::GamePhase::Ongoing
+            || !matches!(self.connection, network::GameConnection::Local)
+        {
+            return false;
+        }
+
+        let side = self.board.turn();
+        if let Some(reason) = self.claimable_draw() {
+            self.draw_offered_by = None;
+            self.end(chesstp::GamePhase::Draw, EndReason::Draw(reason));
+            return true;
+        }
+        match self.draw_offered_by {
+            Some(offerer) if offerer != side => {
+                self.draw_offered_by = None;
+                self.end(
+                    chesstp::GamePhase::Draw,
+                    EndReason::Draw(DrawReason::Agreement),
+                );
+            }
+            _ => {
+                self.draw_offered_by = Some(side);
+                self.transient_warning = Some((
+                    format!("{:?} offers a draw, press D to accept", side),
+                    Instant::now(),
+                ));
+            }
+        }
+        true
+    }
+
+    /// Resign once asked twice within `TRANSIENT_WARNING_DURATION`, see `resign`. Does nothing in
+    /// analysis, where no one is playing to win. Returns whether anything happened.
+    pub fn request_resignation(&mut self) -> bool {
+        if self.game_phase != chesstp::GamePhase::Ongoing
+            || matches!(self.connection, network::GameConnection::Analysis)
+        {
+            return false;
+        }
+
+        if self
+            .resignation_requested_at
+            .is_some_and(|requested_at| requested_at.elapsed() < TRANSIENT_WARNING_DURATION)
+        {
+            self.resignation_requested_at = None;
+            self.resign();
+        } else {
+            self.resignation_requested_at = Some(Instant::now());
+            self.transient_warning = Some(("Resign again to confirm".to_owned(), Instant::now()));
+        }
+        true
+    }
+
+    /// Resign on behalf of the local player, or the side to move in a local game, telling the
+    /// remote in networked games. Returns whether the game was ongoing.
+    pub fn resign(&mut self) -> bool {
+        if self.game_phase != chesstp::GamePhase::Ongoing {
+            return false;
+        }
+
+        let side = self.my_color.unwrap_or(self.board.turn());
+        if let network::GameConnection::Remote(_, _, ref mut stream) = self.connection {
+            let message = chesstp::ResignMessage { color: side };
+            if let Err(error) = stream.write(chesstp::Message::Resign(message)) {
+                println!("Couldn't send resignation: {}", error);
+            }
+        }
+        self.end(
+            chesstp::GamePhase::Win(side.opposite()),
+            EndReason::Resignation,
+        );
+
+        // Drop any selected piece. The finished game keeps the board from taking moves, see
+        // `is_my_turn`.
+        self.turn_phase = TurnPhase::SelectSource;
+        self.potential_dests.clear();
+        self.checking_dests.clear();
+        true
+    }
+
+    /// Play a move for the local player, without going through square selection. Promotes to
+    /// `promotion` if a pawn reaches the last rank, otherwise to whatever the library picks.
+    ///
+    /// Is a no-op which returns `MoveError::GameOver` once the game has ended, and
+    /// `MoveError::WrongPlayer` when it isn't the local player's turn, see `is_my_turn`.
+    pub fn try_move(
+        &mut self,
+        source: Position,
+        dest: Position,
+        promotion: Option<PieceKind>,
+    ) -> Result<MoveOutcome, MoveError> {
+        if self.game_phase != chesstp::GamePhase::Ongoing {
+            return Err(MoveError::GameOver);
+        }
+        if !self.is_my_turn() {
+            return Err(MoveError::WrongPlayer);
+        }
+
+        let mover = self.board.turn();
+        // Sent along with the move, so only for actual promotions.
+        let promotion = promotion.filter(|_| self.board.is_promotion(source, dest));
+
+        let irreversible = self.board.is_irreversible(source, dest);
+        let castling_changed = self.board.may_change_castling_rights(source, dest);
+        let snapshot = Snapshot {
+            board: self.board.inner().clone(),
+            last_move: self.last_move,
+            game_phase: self.game_phase,
+            end_reason: self.end_reason,
+            halfmove_clock: self.halfmove_clock,
+            position_counts: self.position_counts.clone(),
+            draw_offered_by: self.draw_offered_by,
+        };
+        let outcome = self.board.make_move(source, dest, promotion)?;
+        if !matches!(self.connection, network::GameConnection::Remote(..)) {
+            self.undo_stack.push(snapshot);
+        }
+        self.emit(GameEvent::MoveMade {
+            source,
+            dest,
+            outcome,
+        });
+
+        // Moving declines the opponent's draw offer, while one's own offer stands.
+        if self.draw_offered_by != Some(mover) {
+            self.draw_offered_by = None;
+        }
+
+        let draw_rule = self.track_draw_rules(irreversible, castling_changed);
+        if outcome == MoveOutcome::Checkmate {
+            self.end(chesstp::GamePhase::Win(mover), EndReason::Checkmate);
+        } else if !self.board.has_legal_moves(mover.opposite()) {
+            self.end(
+                chesstp::GamePhase::Draw,
+                EndReason::Draw(DrawReason::Stalemate),
+            );
+        } else if let Some(reason) = draw_rule {
+            self.end(chesstp::GamePhase::Draw, EndReason::Draw(reason));
+        }
+
+        self.record_move(source, dest);
+        self.potential_dests.clear();
+        self.checking_dests.clear();
+        self.turn_phase = match self.connection {
+            network::GameConnection::Local | network::GameConnection::Analysis => {
+                TurnPhase::SelectSource
+            }
+            network::GameConnection::Remote(_, _, ref mut stream) => {
+                let move_message = chesstp::MoveMessage {
+                    source,
+                    dest,
+                    promotion,
+                    phase: self.game_phase,
+                    board: self.board.inner().clone().into(),
+                };
+
+                // The failure is shown through `stream.health()` on the next update.
+                if let Err(error) = stream.write(chesstp::Message::Move(move_message)) {
+                    println!("Couldn't send move: {}", error);
+                }
+                TurnPhase::WaitingForRemote
+            }
+        };
+
+        Ok(outcome)
+    }
+
     /// Function which runs general instantenous state updates. Is meant to be called frequently in
     /// some update loop.
     pub fn update(&mut self) {
-        match self.connection {
-            network::GameConnection::Local => {}
-            network::GameConnection::Remote(type_, _, ref mut stream) => loop {
-                match stream.accept().unwrap() {
-                    Some(chesstp::Message::Quit(message)) => {
-                        if &message.message != "" {
-                            println!("Remote quit")
-                        } else {
-                            println!("Remote quit with message: {}", &message.message)
+        // Collect the received messages first, since handling them needs all of `self`.
+        let (messages, health) = match self.connection {
+            network::GameConnection::Local | network::GameConnection::Analysis => return,
+            network::GameConnection::Remote(_, _, ref mut stream) => {
+                if let Err(error) = stream.flush_pending() {
+                    println!("Sending queued messages failed: {}", error);
+                }
+
+                let mut messages = Vec::new();
+                loop {
+                    match stream.accept() {
+                        Ok(Some(message)) => messages.push(message),
+                        Ok(None) => break,
+                        // The stream records the failure in its health, which is reported below.
+                        Err(error) => {
+                            println!("Receiving messages failed: {}", error);
+                            break;
                         }
                     }
-                    Some(chesstp::Message::Move(message)) => {
-                        self.board.set_turn(match type_ {
-                            network::ConnectionType::Server => Color::Black,
-                            network::ConnectionType::Client => Color::White,
+                }
+                if let Some(error) = stream.take_discarded_frame_error() {
+                    self.transient_warning =
+                        Some((format!("Bad message: {}", error), Instant::now()));
+                }
+
+                (messages, stream.health())
+            }
+        };
+
+        for message in messages {
+            match message {
+                chesstp::Message::Quit(message) => {
+                    if message.message.is_empty() {
+                        println!("Remote quit")
+                    } else {
+                        println!("Remote quit with message: {}", &message.message)
+                    }
+                    self.remote_quit_message = Some(message.message.clone());
+
+                    // Leaving an ongoing game forfeits it.
+                    if let Some(local_color) = self.my_color {
+                        let reason = match EndReason::from_quit_message(&message.message) {
+                            Some(EndReason::Timeout) => EndReason::Timeout,
+                            _ => EndReason::Resignation,
+                        };
+                        self.end(chesstp::GamePhase::Win(local_color), reason);
+                    }
+                }
+                chesstp::Message::Resign(message) => {
+                    let local_color = self.my_color.expect("remote games have a local color");
+                    if message.color == local_color {
+                        println!("Ignored resignation on behalf of the local player");
+                        continue;
+                    }
+
+                    println!("Remote resigned");
+                    self.end(chesstp::GamePhase::Win(local_color), EndReason::Resignation);
+                }
+                chesstp::Message::Move(message) => {
+                    let local_color = self.my_color.expect("remote games have a local color");
+
+                    // The checks below replay the move as the remote's, so make sure it was
+                    // actually their turn.
+                    if self.game_phase != chesstp::GamePhase::Ongoing
+                        || self.turn_phase != TurnPhase::WaitingForRemote
+                    {
+                        println!(
+                            "Rejected received move, desync detected: it isn't the remote's turn"
+                        );
+                        self.desync_detected = true;
+                        continue;
+                    }
+
+                    // An impossible position could crash the move generator later on.
+                    if let Err(error) = message.board.validate() {
+                        println!("Rejected received board: {}", error);
+                        continue;
+                    }
+
+                    // Don't adopt a board which doesn't show the move it came with.
+                    let mover = local_color.opposite();
+                    let consistency = message
+                        .check_consistency(mover)
+                        .and_then(|()| match self.board.at(message.source) {
+                            Some(piece) if piece.color == mover => Ok(()),
+                            _ => Err(format!(
+                                "source {:?} has no piece of the mover on the local board",
+                                message.source
+                            )),
+                        })
+                        .and_then(|()| match message.promotion {
+                            // The promoted piece is placed below, which would let any piece turn
+                            // into another.
+                            Some(kind)
+                                if !self.board.is_promotion(message.source, message.dest) =>
+                            {
+                                Err(format!(
+                                    "{:?} to {:?} promotes to {:?} without being a pawn move to \
+                                     the last rank",
+                                    message.source, message.dest, kind
+                                ))
+                            }
+                            _ => Ok(()),
                         });
+                    if let Err(error) = consistency {
+                        println!("Rejected received move, desync detected: {}", error);
+                        self.desync_detected = true;
+                        continue;
+                    }
 
-                        message.board.update_game(self.board.inner_mut());
-                        self.last_move = Some((message.source, message.dest));
+                    if self.verify_remote_moves {
+                        if let Err(error) = self.board.verify_move(
+                            message.source,
+                            message.dest,
+                            message.promotion,
+                            local_color.opposite(),
+                            &message.board,
+                        ) {
+                            // Keep the local board, which at least holds only legal moves.
+                            println!("Rejected received move, desync detected: {}", error);
+                            self.desync_detected = true;
+                            continue;
+                        }
+                    }
 
-                        self.turn_phase = TurnPhase::SelectSource;
-                        self.game_phase = message.phase;
+                    let irreversible = self.board.is_irreversible(message.source, message.dest);
+                    let castling_changed = self
+                        .board
+                        .may_change_castling_rights(message.source, message.dest);
+                    self.board.set_turn(local_color);
+
+                    message.board.update_game(self.board.inner_mut());
+                    // The received board already contains the promoted piece, but don't rely on
+                    // the board being sent to honor the promotion.
+                    if let Some(kind) = message.promotion {
+                        self.board.set_piece(
+                            message.dest,
+                            Some(Piece {
+                                kind,
+                                color: local_color.opposite(),
+                            }),
+                        );
+                    }
+                    let draw_rule = self.track_draw_rules(irreversible, castling_changed);
+                    self.record_move(message.source, message.dest);
+                    self.emit(GameEvent::MoveReceived {
+                        source: message.source,
+                        dest: message.dest,
+                    });
+
+                    self.turn_flash_started_at = Some(Instant::now());
+                    self.turn_phase = TurnPhase::SelectSource;
+                    match message.phase {
+                        chesstp::GamePhase::Ongoing => {}
+                        chesstp::GamePhase::Win(_) => self.end(message.phase, EndReason::Checkmate),
+                        chesstp::GamePhase::Draw => {
+                            // The reason isn't sent, but both sides track the same rules. A draw
+                            // which a rule only made claimable was claimed by the remote.
+                            let reason = if self.board.has_legal_moves(local_color) {
+                                draw_rule
+                                    .or(self.claimable_draw())
+                                    .unwrap_or(DrawReason::Agreement)
+                            } else {
+                                DrawReason::Stalemate
+                            };
+                            self.end(message.phase, EndReason::Draw(reason));
+                        }
                     }
-                    None => break,
                 }
-            },
+            }
+        }
+
+        if health != network::ConnectionHealth::Healthy && !self.connection_lost_reported {
+            self.connection_lost_reported = true;
+            self.emit(GameEvent::ConnectionLost(health));
+        }
+    }
+}
+
+/// The areas of the sidebar which each widget draws into, from top to bottom.
+struct SideBarLayout {
+    /// Shows whose turn it is, or the result once the game is over.
+    turn_area: graphics::Rect,
+    /// The latest moves, filling the space not used by the other areas.
+    move_list_area: graphics::Rect,
+    /// Chat messages. Has no height until there is a chat.
+    #[allow(dead_code)]
+    chat_area: graphics::Rect,
+    /// Buttons like resign.
+    action_area: graphics::Rect,
+    /// Single line warnings, e.g. for a detected desync.
+    status_area: graphics::Rect,
+    /// Who the game is played against, for networked games.
+    connection_area: graphics::Rect,
+}
+
+impl SideBarLayout {
+    /// Lay out a sidebar of the specified width to the right of the board.
+    fn new(board_bounds: graphics::Rect, width: f32) -> Self {
+        static MARGIN: f32 = 30.0;
+        static LINE_HEIGHT: f32 = 30.0;
+        static ACTION_HEIGHT: f32 = 50.0;
+
+        let area = |top: f32, height: f32| graphics::Rect {
+            x: board_bounds.right(),
+            y: top,
+            w: width,
+            h: height,
+        };
+
+        let turn_area = area(board_bounds.top() + MARGIN, 80.0 + LINE_HEIGHT);
+        let connection_area = area(board_bounds.bottom() - 3.0 * LINE_HEIGHT, 3.0 * LINE_HEIGHT);
+        let status_area = area(connection_area.top() - MARGIN - LINE_HEIGHT, LINE_HEIGHT);
+        let action_area = area(status_area.top() - MARGIN - ACTION_HEIGHT, ACTION_HEIGHT);
+        let chat_area = area(action_area.top(), 0.0);
+        let move_list_top = turn_area.bottom() + MARGIN;
+        let move_list_area = area(move_list_top, chat_area.top() - move_list_top);
+
+        Self {
+            turn_area,
+            move_list_area,
+            chat_area,
+            action_area,
+            status_area,
+            connection_area,
         }
     }
 }
@@ -644,68 +2361,517 @@ impl GameState {
 pub struct GameUi {
     state: Arc<RefCell<GameState>>,
     board_bounds: graphics::Rect,
+    side_bar_width: f32,
+    /// Height of the player label below the board, as measured when the UI was created.
+    bottom_label_height: f32,
     square_buttons: [ui::Button; 64],
+    /// The choices of the promotion chooser, ordered like `PROMOTION_CHOICES`. Are only shown, and
+    /// placed on the promoting pawn's file, while a promotion is being chosen.
+    promotion_buttons: [ui::Button; 4],
+    /// Is placed in the sidebar's action area while the game can be resigned, see
+    /// `layout_action_buttons`.
+    resign_button: ui::Button,
+    /// Is placed in the sidebar's action area while there's a move to take back, see
+    /// `layout_action_buttons`.
+    undo_button: ui::Button,
+    assets: Arc<Assets>,
+    /// One instance array per piece graphic, refilled every frame so all pieces of the same kind
+    /// and color are drawn in a single draw call.
+    piece_batches: Vec<(Piece, graphics::InstanceArray)>,
+    /// The `Assets::generation` which `piece_batches` were created from.
+    piece_batches_generation: u64,
+    /// Whether to draw a thin frame around the board.
+    board_frame: bool,
+    /// Whether the sidebar is hidden, showing whose turn it is as a thin bar below the board
+    /// instead.
+    compact: bool,
+    /// Whether pieces are hidden for practicing visualization, see `set_blindfold`.
+    blindfold: bool,
+    /// Whether the pieces are temporarily shown despite `blindfold`.
+    revealed: bool,
+    /// When the board started turning to its current orientation, while it's still turning. Input
+    /// to the board is ignored meanwhile, see `set_orientation`.
+    flip_started_at: Option<Instant>,
 }
 
-impl GameUi {
-    pub fn new(
-        _ctx: &mut Context,
-        top_left: glam::Vec2,
-        assets: &Arc<Assets>,
-        connection: network::GameConnection,
-    ) -> GameResult<Self> {
-        let flip_board = match connection {
-            network::GameConnection::Remote(network::ConnectionType::Server, _, _) => true,
-            _ => false,
-        };
+impl GameUi {
+    pub fn new(
+        ctx: &mut Context,
+        top_left: glam::Vec2,
+        assets: &Arc<Assets>,
+        connection: network::GameConnection,
+    ) -> GameResult<Self> {
+        Self::with_board(
+            ctx,
+            top_left,
+            assets,
+            BoardWrapper::new(chess::game::game_state::new()),
+            connection,
+        )
+    }
+
+    /// Like `GameUi::new`, but starts the game from the specified position instead of the standard
+    /// starting position.
+    pub fn with_board(
+        ctx: &mut Context,
+        top_left: glam::Vec2,
+        assets: &Arc<Assets>,
+        board: BoardWrapper,
+        connection: network::GameConnection,
+    ) -> GameResult<Self> {
+        let state = Arc::new(RefCell::new(GameState::new(board, connection)));
+        let orientation = state.borrow().orientation;
+        let board_bounds = graphics::Rect {
+            x: top_left.x,
+            y: top_left.y + BOARD_Y_MARGIN,
+            w: 100.0 * 8.0,
+            h: 100.0 * 8.0,
+        };
+        let state_ref = &state;
+        let components: Box<[_; 64]> = std::iter::repeat_n(0..8, 8)
+            .enumerate()
+            .flat_map(|(row_index, column_indices)| {
+                column_indices.map(move |column_index| {
+                    let position = Position::new(column_index as u8, row_index as u8)
+                        .expect("indices are < 8");
+
+                    ui::Button::new(
+                        square_bounds(board_bounds, position, orientation),
+                        Square::new(position, state_ref.clone()),
+                    )
+                })
+            })
+            .collect::<Box<[_]>>()
+            .try_into()
+            .ok()
+            .expect("there are 64 position");
+
+        let (_, (bottom_label, _)) =
+            Self::player_labels(ctx, &state.borrow(), board_bounds.w - 2.0 * TEXT_MARGIN)?;
+        let bottom_label_height = bottom_label.measure(ctx)?.y;
+
+        let promotion_buttons = PROMOTION_CHOICES.map(|kind| {
+            ui::Button::new(
+                graphics::Rect::new(0.0, 0.0, 0.0, 0.0),
+                PromotionChoice {
+                    kind,
+                    state: state.clone(),
+                    assets: assets.clone(),
+                },
+            )
+        });
+
+        let resign_state = state.clone();
+        let resign_button = ui::Button::new(
+            graphics::Rect::new(0.0, 0.0, 0.0, 0.0),
+            ui::RoundedButton::new(
+                10.0,
+                ui::MouseColors::new(PALETTE.button, PALETTE.button_hover, PALETTE.button_active),
+                move || {
+                    resign_state.borrow_mut().request_resignation();
+                },
+            ),
+        );
+        let undo_state = state.clone();
+        let undo_button = ui::Button::new(
+            graphics::Rect::new(0.0, 0.0, 0.0, 0.0),
+            ui::RoundedButton::new(
+                10.0,
+                ui::MouseColors::new(PALETTE.button, PALETTE.button_hover, PALETTE.button_active),
+                move || {
+                    undo_state.borrow_mut().undo_move();
+                },
+            ),
+        );
+
+        Ok(Self {
+            state,
+            square_buttons: *components,
+            promotion_buttons,
+            resign_button,
+            undo_button,
+            board_bounds,
+            side_bar_width: DEFAULT_SIDE_BAR_WIDTH,
+            bottom_label_height,
+            assets: assets.clone(),
+            piece_batches: Self::create_piece_batches(ctx, assets),
+            piece_batches_generation: assets.generation(),
+            board_frame: true,
+            compact: false,
+            blindfold: false,
+            revealed: false,
+            flip_started_at: None,
+        })
+    }
+
+    /// Create one instance array per piece graphic, see `piece_batches`.
+    fn create_piece_batches(
+        ctx: &mut Context,
+        assets: &Assets,
+    ) -> Vec<(Piece, graphics::InstanceArray)> {
+        [Color::White, Color::Black]
+            .into_iter()
+            .flat_map(|color| {
+                let pieces = assets.pieces(color);
+                PIECE_KINDS.map(|kind| {
+                    let image = pieces.get(kind).clone();
+                    (
+                        Piece { kind, color },
+                        graphics::InstanceArray::new(ctx, image),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Draw every piece on top of its square, using one draw call per kind and color of piece
+    /// instead of one per piece. Relies on the buttons being ordered like `Position::all`.
+    fn draw_pieces(
+        &mut self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        offset: glam::Vec2,
+    ) {
+        if self.blindfold && !self.revealed {
+            return;
+        }
+
+        // The batches hold the images, so recreate them if a piece set was replaced.
+        let generation = self.assets.generation();
+        if generation != self.piece_batches_generation {
+            self.piece_batches = Self::create_piece_batches(ctx, &self.assets);
+            self.piece_batches_generation = generation;
+        }
+
+        for (_, batch) in &mut self.piece_batches {
+            batch.clear();
+        }
+
+        // Look the sets up once instead of locking them for every piece.
+        let white_pieces = self.assets.pieces(Color::White);
+        let black_pieces = self.assets.pieces(Color::Black);
+
+        let state = self.state.borrow();
+        for (position, button) in Position::all().zip(&self.square_buttons) {
+            let Some(piece) = state.board.at(position) else {
+                continue;
+            };
+            let Some((_, batch)) = self
+                .piece_batches
+                .iter_mut()
+                .find(|(batch_piece, _)| *batch_piece == piece)
+            else {
+                continue;
+            };
+
+            let mut bounds = piece_bounds(button.bounds());
+            bounds.translate(offset);
+            let image = match piece.color {
+                Color::White => white_pieces.get(piece.kind),
+                Color::Black => black_pieces.get(piece.kind),
+            };
+            let scale = glam::vec2(
+                bounds.w / image.width() as f32,
+                bounds.h / image.height() as f32,
+            );
+            batch.push(graphics::DrawParam::new().dest(bounds.point()).scale(scale));
+        }
+
+        for (_, batch) in &self.piece_batches {
+            if !batch.instances().is_empty() {
+                canvas.draw(batch, graphics::DrawParam::new());
+            }
+        }
+    }
+
+    /// Draw a banner across the middle of the board announcing why the game ended, once it has.
+    /// In networked games it's worded from the local player's perspective.
+    fn draw_end_banner(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        offset: glam::Vec2,
+    ) -> GameResult {
+        let state = self.state.borrow();
+        let Some(reason) = state.end_reason else {
+            return Ok(());
+        };
+
+        let (subtitle, title_color) = match (reason, state.game_phase, state.my_color) {
+            (EndReason::Draw(draw_reason), _, _) => (
+                format!("by {}", draw_reason.description()),
+                PALETTE.text_neutral,
+            ),
+            (EndReason::Resignation, chesstp::GamePhase::Win(winner), Some(local))
+                if winner == local =>
+            {
+                (
+                    opponent_left_text(state.remote_quit_message.as_deref()),
+                    PALETTE.text_neutral,
+                )
+            }
+            (_, chesstp::GamePhase::Win(winner), Some(local)) if winner == local => {
+                ("You won".to_owned(), PALETTE.text_neutral)
+            }
+            (_, chesstp::GamePhase::Win(_), Some(_)) => {
+                ("You lost".to_owned(), PALETTE.text_warning)
+            }
+            (_, chesstp::GamePhase::Win(Color::White), None) => {
+                ("White won".to_owned(), PALETTE.text_neutral)
+            }
+            (_, chesstp::GamePhase::Win(Color::Black), None) => {
+                ("Black won".to_owned(), PALETTE.text_neutral)
+            }
+            _ => ("Nobody won".to_owned(), PALETTE.text_neutral),
+        };
+
+        let mut bounds = graphics::Rect {
+            x: self.board_bounds.x,
+            y: self.board_bounds.center().y - 80.0,
+            w: self.board_bounds.w,
+            h: 160.0,
+        };
+        bounds.translate(offset);
+        let background = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            bounds,
+            PALETTE.modal_background,
+        )?;
+        canvas.draw(&background, graphics::DrawParam::new());
+
+        let mut title_text = graphics::Text::new(reason.title());
+        title_text
+            .set_scale(graphics::PxScale::from(typography::HEADING))
+            .set_layout(graphics::TextLayout {
+                h_align: graphics::TextAlign::Middle,
+                v_align: graphics::TextAlign::Begin,
+            });
+        canvas.draw(
+            &title_text,
+            graphics::DrawParam::new()
+                .color(title_color)
+                .dest(glam::vec2(bounds.center().x, bounds.top() + 20.0)),
+        );
+
+        let mut subtitle_text = graphics::Text::new(subtitle);
+        subtitle_text
+            .set_scale(graphics::PxScale::from(typography::BODY))
+            .set_layout(graphics::TextLayout {
+                h_align: graphics::TextAlign::Middle,
+                v_align: graphics::TextAlign::Begin,
+            });
+        canvas.draw(
+            &subtitle_text,
+            graphics::DrawParam::new()
+                .color(PALETTE.text_subtle)
+                .dest(glam::vec2(bounds.center().x, bounds.top() + 105.0)),
+        );
+
+        Ok(())
+    }
+
+    /// Returns the labels and colors of the players shown above and below the board respectively.
+    fn player_labels(
+        ctx: &Context,
+        state: &GameState,
+        max_width: f32,
+    ) -> GameResult<(
+        (graphics::Text, graphics::Color),
+        (graphics::Text, graphics::Color),
+    )> {
+        let label = |color: Color, name: &str| {
+            let content = if state.my_color == Some(color) {
+                format!("{} (you)", name)
+            } else {
+                name.to_owned()
+            };
+            fitted_text(ctx, &content, typography::SUBHEADING, max_width)
+        };
+
+        let black = (label(Color::Black, "Black")?, PALETTE.board_square_black);
+        let white = (label(Color::White, "White")?, PALETTE.board_square_white);
+        Ok(match state.orientation {
+            Orientation::BlackAtBottom => (white, black),
+            Orientation::WhiteAtBottom => (black, white),
+        })
+    }
+
+    /// Returns the color to flash the turn indicator with, while it's fading after a received move
+    /// made it the local player's turn.
+    fn turn_flash_color(&self) -> Option<graphics::Color> {
+        let state = self.state.borrow();
+        if state.game_phase != chesstp::GamePhase::Ongoing {
+            return None;
+        }
+
+        let alpha = turn_flash_alpha(state.turn_flash_started_at?.elapsed());
+        (alpha > 0.0).then(|| {
+            let mut color = PALETTE.turn_flash;
+            color.a *= alpha;
+            color
+        })
+    }
+
+    /// Returns a warning about connection problems or a detected desync, if there is one, or else
+    /// a recent transient warning.
+    fn status_warning(&self) -> Option<String> {
+        let state = self.state.borrow();
+        match state.connection {
+            network::GameConnection::Remote(_, _, ref stream) => match stream.health() {
+                network::ConnectionHealth::Healthy => None,
+                network::ConnectionHealth::PeerClosed => Some("Opponent disconnected"),
+                network::ConnectionHealth::WriteFailed => Some("Sending moves failed"),
+                network::ConnectionHealth::ReadFailed => Some("Receiving moves failed"),
+                network::ConnectionHealth::Stalled => Some("Opponent stopped mid-message"),
+            },
+            network::GameConnection::Local | network::GameConnection::Analysis => None,
+        }
+        .or(state.desync_detected.then_some("Desync detected"))
+        .map(str::to_owned)
+        .or_else(|| {
+            state
+                .transient_warning
+                .as_ref()
+                .filter(|(_, shown_at)| shown_at.elapsed() < TRANSIENT_WARNING_DURATION)
+                .map(|(warning, _)| warning.clone())
+        })
+    }
+
+    /// Draw the compact replacement for the sidebar: a thin bar below the board in the color of
+    /// the side to move, or of the winner once the game is over, with any status warning next to
+    /// the bottom player label.
+    fn draw_turn_bar(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        offset: glam::Vec2,
+    ) -> GameResult {
+        let label_bottom = self.board_bounds.bottom() + BOTTOM_LABEL_GAP + self.bottom_label_height;
+
+        let side_color = |color: Color| match color {
+            Color::White => PALETTE.board_square_white,
+            Color::Black => PALETTE.board_square_black,
+        };
+        let bar_color = {
+            let state = self.state.borrow();
+            match state.game_phase {
+                chesstp::GamePhase::Ongoing => side_color(state.board.turn()),
+                chesstp::GamePhase::Win(winner) => side_color(winner),
+                chesstp::GamePhase::Draw => PALETTE.text_subtle,
+            }
+        };
+        let mut bar_bounds = graphics::Rect {
+            x: self.board_bounds.x,
+            y: label_bottom + TURN_BAR_GAP,
+            w: self.board_bounds.w,
+            h: TURN_BAR_HEIGHT,
+        };
+        bar_bounds.translate(offset);
+        if let Some(flash_color) = self.turn_flash_color() {
+            let mut flash_bounds = bar_bounds;
+            flash_bounds.translate(-glam::Vec2::splat(TURN_BAR_GAP / 2.0));
+            flash_bounds.w += TURN_BAR_GAP;
+            flash_bounds.h += TURN_BAR_GAP;
+            let flash = graphics::Mesh::new_rounded_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                flash_bounds,
+                flash_bounds.h / 2.0,
+                flash_color,
+            )?;
+            canvas.draw(&flash, graphics::DrawParam::new());
+        }
+        let bar = graphics::Mesh::new_rounded_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            bar_bounds,
+            TURN_BAR_HEIGHT / 2.0,
+            bar_color,
+        )?;
+        canvas.draw(&bar, graphics::DrawParam::new());
+
+        if let Some(status) = self.status_warning() {
+            let mut status_text = graphics::Text::new(status);
+            status_text
+                .set_scale(graphics::PxScale::from(typography::SMALL))
+                .set_layout(graphics::TextLayout {
+                    h_align: graphics::TextAlign::End,
+                    v_align: graphics::TextAlign::End,
+                });
+            canvas.draw(
+                &status_text,
+                graphics::DrawParam::new()
+                    .color(PALETTE.text_warning)
+                    .dest(glam::vec2(self.board_bounds.right() - 10.0, label_bottom) + offset),
+            );
+        }
+
+        Ok(())
+    }
 
-        let state = Arc::new(RefCell::new(GameState::new(
-            BoardWrapper::new(chess::game::game_state::new()),
-            connection,
-        )));
-        let board_bounds = graphics::Rect {
-            x: top_left.x,
-            y: top_left.y + BOARD_Y_MARGIN,
-            w: 100.0 * 8.0,
-            h: 100.0 * 8.0,
+    /// Place the promotion chooser on the file of the pawn being promoted, if a promotion is being
+    /// chosen. Returns whether one is.
+    fn layout_promotion_buttons(&mut self) -> bool {
+        let (dest, orientation) = {
+            let state = self.state.borrow();
+            match state.turn_phase {
+                TurnPhase::ChoosePromotion { dest, .. } => (dest, state.orientation),
+                _ => return false,
+            }
         };
-        let state_ref = &state;
-        let components: Box<[_; 64]> = std::iter::repeat_n(0..8, 8)
-            .enumerate()
-            .flat_map(|(row_index, column_indices)| {
-                column_indices.map(move |column_index| {
-                    let position = Position::new(column_index as u8, row_index as u8)
-                        .expect("indices are < 8");
 
-                    let position_indices: glam::Vec2 = position.into();
-                    let position_indices = if flip_board {
-                        position_indices
-                    } else {
-                        (glam::vec2(0.0, 7.0) - position_indices) * glam::vec2(-1.0, 1.0)
-                    };
-
-                    let mut square_bounds = board_bounds.clone();
-                    square_bounds.scale(1.0 / 8.0, 1.0 / 8.0);
+        for (index, button) in self.promotion_buttons.iter_mut().enumerate() {
+            button.set_bounds(promotion_choice_bounds(
+                self.board_bounds,
+                dest,
+                orientation,
+                index as u8,
+            ));
+        }
+        true
+    }
 
-                    let square_size: glam::Vec2 = square_bounds.size().into();
-                    square_bounds.translate(position_indices * square_size);
+    /// Place the buttons which are shown side by side in the sidebar's action area, returning
+    /// whether the resign and undo buttons are shown, in that order. Resign is shown while the game
+    /// is ongoing and not an analysis, and undo while there's a move to take back. Neither is while
+    /// the sidebar is hidden.
+    fn layout_action_buttons(&mut self) -> (bool, bool) {
+        static BUTTON_X_MARGIN: f32 = 30.0;
+        static BUTTON_GAP: f32 = 10.0;
 
-                    ui::Button::new(
-                        square_bounds,
-                        Square::new(position, state_ref.clone(), assets.clone()),
-                    )
-                })
-            })
-            .collect::<Box<[_]>>()
-            .try_into()
-            .ok()
-            .expect("there are 64 position");
+        if self.compact {
+            return (false, false);
+        }
+        let (show_resign, show_undo) = {
+            let state = self.state.borrow();
+            (
+                state.game_phase == chesstp::GamePhase::Ongoing
+                    && !matches!(state.connection, network::GameConnection::Analysis),
+                !state.undo_stack.is_empty(),
+            )
+        };
 
-        Ok(Self {
-            state,
-            square_buttons: *components,
-            board_bounds,
-        })
+        let shown = usize::from(show_resign) + usize::from(show_undo);
+        let area = SideBarLayout::new(self.board_bounds, self.side_bar_width).action_area;
+        let width = (area.w - 2.0 * BUTTON_X_MARGIN - BUTTON_GAP * shown.saturating_sub(1) as f32)
+            / shown.max(1) as f32;
+        let mut x = area.x + BUTTON_X_MARGIN;
+        for (button, show) in [
+            (&mut self.undo_button, show_undo),
+            (&mut self.resign_button, show_resign),
+        ] {
+            if show {
+                button.set_bounds(graphics::Rect {
+                    x,
+                    y: area.y,
+                    w: width,
+                    h: area.h,
+                });
+                x += width + BUTTON_GAP;
+            }
+        }
+        (show_resign, show_undo)
     }
 
     pub fn update_with_press_state(
@@ -713,6 +2879,37 @@ impl GameUi {
         position: glam::Vec2,
         press_state: PressState,
     ) -> bool {
+        // The squares are out of place while the board is turning.
+        if self.flip_started_at.is_some() {
+            return false;
+        }
+
+        // The promotion chooser blocks the squares until a piece has been chosen.
+        if self.layout_promotion_buttons() {
+            for button in self.promotion_buttons.iter_mut() {
+                if button.update_with_press_state(position, press_state) {
+                    return true;
+                }
+            }
+            return false;
+        }
+
+        let (show_resign, show_undo) = self.layout_action_buttons();
+        if show_resign
+            && self
+                .resign_button
+                .update_with_press_state(position, press_state)
+        {
+            return true;
+        }
+        if show_undo
+            && self
+                .undo_button
+                .update_with_press_state(position, press_state)
+        {
+            return true;
+        }
+
         for button in self.square_buttons.iter_mut() {
             if button.update_with_press_state(position, press_state) {
                 return true;
@@ -723,8 +2920,22 @@ impl GameUi {
     }
 
     pub fn update_with_mouse_position(&mut self, position: glam::Vec2) {
-        for button in self.square_buttons.iter_mut() {
-            button.update_with_mouse_position(position);
+        if self.flip_started_at.is_none() {
+            for button in self.square_buttons.iter_mut() {
+                button.update_with_mouse_position(position);
+            }
+        }
+        if self.layout_promotion_buttons() {
+            for button in self.promotion_buttons.iter_mut() {
+                button.update_with_mouse_position(position);
+            }
+        }
+        let (show_resign, show_undo) = self.layout_action_buttons();
+        if show_resign {
+            self.resign_button.update_with_mouse_position(position);
+        }
+        if show_undo {
+            self.undo_button.update_with_mouse_position(position);
         }
     }
 
@@ -734,162 +2945,309 @@ impl GameUi {
         canvas: &mut graphics::Canvas,
         offset: glam::Vec2,
     ) -> GameResult {
+        // Find which squares to tint, if a king is hovered. Relies on the buttons being ordered
+        // like `Position::all`.
+        let hovered_king = Position::all()
+            .zip(&self.square_buttons)
+            .find(|(_, button)| button.hovered())
+            .and_then(|(position, _)| self.state.borrow().board.at(position))
+            .filter(|piece| piece.kind == PieceKind::King);
+        let threatened_squares = match hovered_king {
+            Some(king) => self
+                .state
+                .borrow()
+                .board
+                .attacked_squares(king.color.opposite()),
+            None => HashSet::new(),
+        };
+        self.state.borrow_mut().threatened_squares = threatened_squares;
+
         // Draw board squares.
         for component in &self.square_buttons {
             component.draw(ctx, canvas, offset)?;
         }
 
-        // ctx.gfx.window().scale_factor()
+        // Draw frame just outside the board, following its rounded corners.
+        if self.board_frame {
+            let mut frame_bounds = self.board_bounds;
+            frame_bounds.translate(offset - glam::Vec2::splat(BOARD_FRAME_WIDTH / 2.0));
+            frame_bounds.w += BOARD_FRAME_WIDTH;
+            frame_bounds.h += BOARD_FRAME_WIDTH;
+            let frame = graphics::Mesh::new_rounded_rectangle(
+                ctx,
+                graphics::DrawMode::stroke(BOARD_FRAME_WIDTH),
+                frame_bounds,
+                BOARD_CORNER_RADIUS + BOARD_FRAME_WIDTH / 2.0,
+                PALETTE.board_frame,
+            )?;
+            canvas.draw(&frame, graphics::DrawParam::new());
+        }
 
-        Self::size();
+        self.draw_pieces(ctx, canvas, offset);
 
-        // Draw player labels.
-        let mut white_label = graphics::Text::new(match self.state.borrow().connection {
-            network::GameConnection::Remote(network::ConnectionType::Client, _, _) => "White (you)",
-            _ => "White",
-        });
-        white_label.set_scale(graphics::PxScale::from(35.0));
-        let mut black_label = graphics::Text::new(match self.state.borrow().connection {
-            network::GameConnection::Remote(network::ConnectionType::Server, _, _) => "Black (you)",
-            _ => "Black",
-        });
-        black_label.set_scale(graphics::PxScale::from(35.0));
-
-        let ((top_label, top_color), (bottom_label, bottom_color)) = {
-            let black = (black_label, PALETTE.board_square_black);
-            let white = (white_label, PALETTE.board_square_white);
-            match self.state.borrow().connection {
-                network::GameConnection::Remote(network::ConnectionType::Server, _, _) => {
-                    (white, black)
-                }
-                _ => (black, white),
+        // Draw the promotion chooser over the dimmed board.
+        if self.layout_promotion_buttons() {
+            let mut backdrop_bounds = self.board_bounds;
+            backdrop_bounds.translate(offset);
+            let backdrop = graphics::Mesh::new_rounded_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                backdrop_bounds,
+                BOARD_CORNER_RADIUS,
+                PALETTE.modal_backdrop,
+            )?;
+            canvas.draw(&backdrop, graphics::DrawParam::new());
+
+            for button in &self.promotion_buttons {
+                button.draw(ctx, canvas, offset)?;
             }
-        };
+        }
+
+        // Draw player labels.
+        let ((top_label, top_color), (bottom_label, bottom_color)) = Self::player_labels(
+            ctx,
+            &self.state.borrow(),
+            self.board_bounds.w - 2.0 * TEXT_MARGIN,
+        )?;
 
         canvas.draw(
             &top_label,
             graphics::DrawParam::new()
-                .dest(self.board_bounds.top_left() + glam::vec2(10.0, -35.0 - 5.0) + offset)
+                .dest(
+                    self.board_bounds.top_left()
+                        + glam::vec2(10.0, -typography::SUBHEADING - 5.0)
+                        + offset,
+                )
                 .color(top_color),
         );
         canvas.draw(
             &bottom_label,
             graphics::DrawParam::new()
-                .dest(self.board_bounds.bottom_left() + glam::vec2(10.0, 5.0 + 3.0) + offset)
+                .dest(self.board_bounds.bottom_left() + glam::vec2(10.0, BOTTOM_LABEL_GAP) + offset)
                 .color(bottom_color),
         );
 
-        let side_bar_bounds = graphics::Rect {
-            x: self.board_bounds.right(),
-            y: self.board_bounds.top(),
-            w: 300.0,
-            h: self.board_bounds.h,
-        };
-        static SIDE_BAR_TOP_MARGIN: f32 = 30.0;
+        self.draw_end_banner(ctx, canvas, offset)?;
+
+        if self.compact {
+            return self.draw_turn_bar(ctx, canvas, offset);
+        }
+
+        let layout = SideBarLayout::new(self.board_bounds, self.side_bar_width);
+
+        // Flash behind the turn display when it just became the local player's turn.
+        if let Some(flash_color) = self.turn_flash_color() {
+            let mut flash_bounds = layout.turn_area;
+            flash_bounds.translate(offset);
+            let flash = graphics::Mesh::new_rounded_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                flash_bounds,
+                10.0,
+                flash_color,
+            )?;
+            canvas.draw(&flash, graphics::DrawParam::new());
+        }
 
         // Draw turn/game phase display.
-        match self.state.borrow().game_phase {
+        let state = self.state.borrow();
+        match state.game_phase {
+            chesstp::GamePhase::Ongoing
+                if matches!(state.connection, network::GameConnection::Analysis) =>
+            {
+                draw_sidebar_headline(
+                    ctx,
+                    canvas,
+                    layout.turn_area,
+                    offset,
+                    "Analysis",
+                    PALETTE.text_neutral,
+                    Some("either side moves"),
+                )?;
+            }
             chesstp::GamePhase::Ongoing => {
-                let (turn_str, turn_color) = match self.state.borrow().board.turn() {
+                let (turn_str, turn_color) = match state.board.turn() {
                     Color::White => ("White", PALETTE.board_square_white),
                     Color::Black => ("Black", PALETTE.board_square_black),
                 };
-                let mut turn_text = graphics::Text::new(turn_str);
-                turn_text
-                    .set_scale(graphics::PxScale::from(80.0))
-                    .set_layout(graphics::TextLayout {
-                        h_align: graphics::TextAlign::Middle,
-                        v_align: graphics::TextAlign::Begin,
-                    });
-
-                canvas.draw(
-                    &turn_text,
-                    graphics::DrawParam::new().color(turn_color).dest(
-                        glam::vec2(
-                            side_bar_bounds.center().x,
-                            side_bar_bounds.top() + SIDE_BAR_TOP_MARGIN,
-                        ) + offset,
-                    ),
-                );
-
-                let mut subtitle_text = graphics::Text::new("to move");
-                subtitle_text
-                    .set_scale(graphics::PxScale::from(30.0))
-                    .set_layout(graphics::TextLayout {
-                        h_align: graphics::TextAlign::Middle,
-                        v_align: graphics::TextAlign::Begin,
-                    });
-
-                canvas.draw(
-                    &subtitle_text,
-                    graphics::DrawParam::new().color(PALETTE.text_subtle).dest(
-                        glam::vec2(
-                            side_bar_bounds.center().x,
-                            side_bar_bounds.top() + SIDE_BAR_TOP_MARGIN + 80.0,
-                        ) + offset,
-                    ),
-                );
+                let subtitle_text = draw_sidebar_headline(
+                    ctx,
+                    canvas,
+                    layout.turn_area,
+                    offset,
+                    turn_str,
+                    turn_color,
+                    Some("to move"),
+                )?;
+
+                // Animate dots after the subtitle while the remote is thinking, so it's clear
+                // that the app isn't frozen.
+                if let (TurnPhase::WaitingForRemote, Some(subtitle_text)) =
+                    (state.turn_phase, subtitle_text)
+                {
+                    let subtitle_size = subtitle_text.measure(ctx)?;
+                    for dot in 0..waiting_dot_count(ctx.time.time_since_start()) {
+                        let center = glam::vec2(
+                            layout.turn_area.center().x
+                                + subtitle_size.x / 2.0
+                                + 10.0
+                                + dot as f32 * 12.0,
+                            layout.turn_area.top() + typography::HEADING + subtitle_size.y * 0.7,
+                        );
+                        let dot_mesh = graphics::Mesh::new_circle(
+                            ctx,
+                            graphics::DrawMode::fill(),
+                            center + offset,
+                            4.0,
+                            0.5,
+                            PALETTE.text_subtle,
+                        )?;
+                        canvas.draw(&dot_mesh, graphics::DrawParam::new());
+                    }
+                }
             }
             chesstp::GamePhase::Win(player) => {
                 let (player_str, player_color) = match player {
                     Color::White => ("White", PALETTE.board_square_white),
                     Color::Black => ("Black", PALETTE.board_square_black),
                 };
-                let mut player_text = graphics::Text::new(player_str);
-                player_text
-                    .set_scale(graphics::PxScale::from(80.0))
-                    .set_layout(graphics::TextLayout {
-                        h_align: graphics::TextAlign::Middle,
-                        v_align: graphics::TextAlign::Begin,
-                    });
+                draw_sidebar_headline(
+                    ctx,
+                    canvas,
+                    layout.turn_area,
+                    offset,
+                    player_str,
+                    player_color,
+                    Some("won"),
+                )?;
+            }
+            chesstp::GamePhase::Draw => {
+                let subtitle = match state.end_reason {
+                    Some(EndReason::Draw(reason)) => Some(format!("by {}", reason.description())),
+                    _ => None,
+                };
+                draw_sidebar_headline(
+                    ctx,
+                    canvas,
+                    layout.turn_area,
+                    offset,
+                    "Draw",
+                    PALETTE.text_neutral,
+                    subtitle.as_deref(),
+                )?;
+            }
+        }
+        drop(state);
 
+        // Draw move list, showing the latest moves which fit.
+        {
+            static MOVE_LINE_HEIGHT: f32 = 28.0;
+
+            let state = self.state.borrow();
+            let max_lines = (layout.move_list_area.h / MOVE_LINE_HEIGHT) as usize;
+            let first_shown = state.moves.len().saturating_sub(max_lines);
+
+            for (line, (index, record)) in
+                state.moves.iter().enumerate().skip(first_shown).enumerate()
+            {
+                let y = layout.move_list_area.top() + line as f32 * MOVE_LINE_HEIGHT;
+                let move_number = if index % 2 == 0 {
+                    format!("{}.", index / 2 + 1)
+                } else {
+                    format!("{}...", index / 2 + 1)
+                };
+
+                let mut move_text = graphics::Text::new(format!(
+                    "{} {}{}",
+                    move_number,
+                    record.source.to_string(false),
+                    record.dest.to_string(false)
+                ));
+                move_text.set_scale(graphics::PxScale::from(typography::SMALL));
                 canvas.draw(
-                    &player_text,
-                    graphics::DrawParam::new().color(player_color).dest(
-                        glam::vec2(
-                            side_bar_bounds.center().x,
-                            side_bar_bounds.top() + SIDE_BAR_TOP_MARGIN,
-                        ) + offset,
-                    ),
+                    &move_text,
+                    graphics::DrawParam::new()
+                        .color(PALETTE.text_neutral)
+                        .dest(glam::vec2(layout.move_list_area.left() + 30.0, y) + offset),
                 );
 
-                let mut subtitle_text = graphics::Text::new("won");
-                subtitle_text
-                    .set_scale(graphics::PxScale::from(30.0))
+                let mut duration_text = graphics::Text::new(format_move_duration(record.duration));
+                duration_text
+                    .set_scale(graphics::PxScale::from(typography::SMALL))
                     .set_layout(graphics::TextLayout {
-                        h_align: graphics::TextAlign::Middle,
+                        h_align: graphics::TextAlign::End,
                         v_align: graphics::TextAlign::Begin,
                     });
-
                 canvas.draw(
-                    &subtitle_text,
-                    graphics::DrawParam::new().color(PALETTE.text_subtle).dest(
-                        glam::vec2(
-                            side_bar_bounds.center().x,
-                            side_bar_bounds.top() + SIDE_BAR_TOP_MARGIN + 80.0,
-                        ) + offset,
-                    ),
+                    &duration_text,
+                    graphics::DrawParam::new()
+                        .color(PALETTE.text_subtle)
+                        .dest(glam::vec2(layout.move_list_area.right() - 30.0, y) + offset),
                 );
             }
-            chesstp::GamePhase::Draw => {
-                let mut draw_text = graphics::Text::new("Draw");
-                draw_text
-                    .set_scale(graphics::PxScale::from(80.0))
-                    .set_layout(graphics::TextLayout {
-                        h_align: graphics::TextAlign::Middle,
-                        v_align: graphics::TextAlign::Begin,
-                    });
+        }
 
-                canvas.draw(
-                    &draw_text,
-                    graphics::DrawParam::new().color(PALETTE.text_neutral).dest(
-                        glam::vec2(
-                            side_bar_bounds.center().x,
-                            side_bar_bounds.top() + SIDE_BAR_TOP_MARGIN,
-                        ) + offset,
-                    ),
-                );
-            }
+        // Draw connection problems, or a desync warning.
+        if let Some(status) = self.status_warning() {
+            // Long warnings wrap upwards, into the free space below the move list.
+            let mut status_text = graphics::Text::new(status);
+            status_text
+                .set_scale(graphics::PxScale::from(typography::BODY))
+                .set_bounds(glam::vec2(layout.status_area.w - 20.0, f32::INFINITY))
+                .set_layout(graphics::TextLayout {
+                    h_align: graphics::TextAlign::Middle,
+                    v_align: graphics::TextAlign::End,
+                });
+
+            canvas.draw(
+                &status_text,
+                graphics::DrawParam::new().color(PALETTE.text_warning).dest(
+                    glam::vec2(layout.status_area.center().x, layout.status_area.bottom()) + offset,
+                ),
+            );
+        }
+
+        // Draw the action buttons. The resign button's label asks for confirmation after the
+        // first press.
+        let (show_resign, show_undo) = self.layout_action_buttons();
+        if show_undo {
+            self.undo_button.draw(ctx, canvas, offset)?;
+
+            let mut label = graphics::Text::new("Undo");
+            label
+                .set_scale(graphics::PxScale::from(typography::BODY))
+                .set_layout(graphics::TextLayout::center());
+            let center = self.undo_button.bounds().center();
+            canvas.draw(
+                &label,
+                graphics::DrawParam::new()
+                    .color(PALETTE.text_neutral)
+                    .dest(glam::vec2(center.x, center.y) + offset),
+            );
+        }
+        if show_resign {
+            self.resign_button.draw(ctx, canvas, offset)?;
+
+            let confirming = self
+                .state
+                .borrow()
+                .resignation_requested_at
+                .is_some_and(|requested_at| requested_at.elapsed() < TRANSIENT_WARNING_DURATION);
+            let mut label = graphics::Text::new(if confirming {
+                "Confirm resignation"
+            } else {
+                "Resign"
+            });
+            label
+                .set_scale(graphics::PxScale::from(typography::BODY))
+                .set_layout(graphics::TextLayout::center());
+            let center = self.resign_button.bounds().center();
+            canvas.draw(
+                &label,
+                graphics::DrawParam::new()
+                    .color(PALETTE.text_neutral)
+                    .dest(glam::vec2(center.x, center.y) + offset),
+            );
         }
 
         // Draw connection info
@@ -900,7 +3258,7 @@ impl GameUi {
                     network::ConnectionType::Client => "running client",
                 });
                 type_text
-                    .set_scale(graphics::PxScale::from(30.0))
+                    .set_scale(graphics::PxScale::from(typography::BODY))
                     .set_layout(graphics::TextLayout {
                         h_align: graphics::TextAlign::Middle,
                         v_align: graphics::TextAlign::Begin,
@@ -908,22 +3266,26 @@ impl GameUi {
 
                 let mut connection_text = graphics::Text::new("connected to");
                 connection_text
-                    .set_scale(graphics::PxScale::from(30.0))
+                    .set_scale(graphics::PxScale::from(typography::BODY))
                     .set_layout(graphics::TextLayout {
                         h_align: graphics::TextAlign::Middle,
                         v_align: graphics::TextAlign::Begin,
                     });
 
-                let mut addr_text = graphics::Text::new(match connection_type {
-                    network::ConnectionType::Server => format!("{}", remote_addr.ip()),
-                    network::ConnectionType::Client => format!("{}", remote_addr),
+                // IPv6 addresses can be wider than the sidebar.
+                let mut addr_text = fitted_text(
+                    ctx,
+                    &match connection_type {
+                        network::ConnectionType::Server => format!("{}", remote_addr.ip()),
+                        network::ConnectionType::Client => format!("{}", remote_addr),
+                    },
+                    typography::BODY,
+                    layout.connection_area.w - 2.0 * TEXT_MARGIN,
+                )?;
+                addr_text.set_layout(graphics::TextLayout {
+                    h_align: graphics::TextAlign::Middle,
+                    v_align: graphics::TextAlign::Begin,
                 });
-                addr_text
-                    .set_scale(graphics::PxScale::from(30.0))
-                    .set_layout(graphics::TextLayout {
-                        h_align: graphics::TextAlign::Middle,
-                        v_align: graphics::TextAlign::Begin,
-                    });
 
                 canvas.draw(
                     &type_text,
@@ -931,8 +3293,8 @@ impl GameUi {
                         .color(PALETTE.board_square_white)
                         .dest(
                             glam::vec2(
-                                side_bar_bounds.center().x,
-                                side_bar_bounds.bottom() - SIDE_BAR_TOP_MARGIN - 30.0 - 30.0,
+                                layout.connection_area.center().x,
+                                layout.connection_area.top(),
                             ) + offset,
                         ),
                 );
@@ -943,8 +3305,8 @@ impl GameUi {
                         .color(PALETTE.board_square_white)
                         .dest(
                             glam::vec2(
-                                side_bar_bounds.center().x,
-                                side_bar_bounds.bottom() - SIDE_BAR_TOP_MARGIN - 30.0,
+                                layout.connection_area.center().x,
+                                layout.connection_area.top() + 30.0,
                             ) + offset,
                         ),
                 );
@@ -953,8 +3315,8 @@ impl GameUi {
                     &addr_text,
                     graphics::DrawParam::new().color(PALETTE.text_subtle).dest(
                         glam::vec2(
-                            side_bar_bounds.center().x,
-                            side_bar_bounds.bottom() - SIDE_BAR_TOP_MARGIN,
+                            layout.connection_area.center().x,
+                            layout.connection_area.top() + 60.0,
                         ) + offset,
                     ),
                 );
@@ -967,14 +3329,168 @@ impl GameUi {
 
     pub fn update(&mut self) {
         self.state.borrow_mut().update();
+
+        let (auto_flip, orientation, turn, game_phase) = {
+            let state = self.state.borrow();
+            (
+                state.auto_flip,
+                state.orientation,
+                state.board.turn(),
+                state.game_phase,
+            )
+        };
+        // Keep the board as it was when the game ended, so the final position reads the same way
+        // as the last move.
+        if auto_flip
+            && game_phase == chesstp::GamePhase::Ongoing
+            && orientation != Orientation::facing(turn)
+        {
+            self.set_orientation(Orientation::facing(turn));
+        }
+
+        self.layout_flipping_squares();
+    }
+
+    /// Turn the board to `orientation`, moving every square, and with it its labels and
+    /// highlights, to its new place. The squares are animated there over `FLIP_DURATION`, see
+    /// `layout_flipping_squares`.
+    fn set_orientation(&mut self, orientation: Orientation) {
+        self.state.borrow_mut().orientation = orientation;
+        self.flip_started_at = Some(Instant::now());
+        self.layout_flipping_squares();
+    }
+
+    /// Move the squares to where they're drawn this frame while the board is turning, or to their
+    /// place in the current orientation once it has turned. Hit-testing only happens once it has,
+    /// so it always uses the final places.
+    fn layout_flipping_squares(&mut self) {
+        let Some(started_at) = self.flip_started_at else {
+            return;
+        };
+        let progress = flip_progress(started_at.elapsed());
+        if progress >= 1.0 {
+            self.flip_started_at = None;
+        }
+
+        let orientation = self.state.borrow().orientation;
+        for (position, button) in Position::all().zip(self.square_buttons.iter_mut()) {
+            let final_bounds = square_bounds(self.board_bounds, position, orientation);
+            button.set_bounds(if self.flip_started_at.is_some() {
+                flipping_square_bounds(self.board_bounds, final_bounds, progress)
+            } else {
+                final_bounds
+            });
+        }
+    }
+
+    /// Turn the board around. Only affects how the board is shown, never the game. Turns off
+    /// automatic flipping, since it would undo this right away.
+    pub fn flip(&mut self) {
+        let orientation = {
+            let mut state = self.state.borrow_mut();
+            state.auto_flip = false;
+            state.orientation.flipped()
+        };
+        self.set_orientation(orientation);
+    }
+
+    /// Set whether the board turns to face the side to move after every move, for two players
+    /// sharing the screen in a local game. Disabled by default.
+    pub fn set_auto_flip(&mut self, enabled: bool) {
+        self.state.borrow_mut().auto_flip = enabled;
+    }
+
+    /// Set whether received moves are checked against the received board, which costs a move
+    /// simulation per received move. Enabled by default.
+    pub fn set_verify_remote_moves(&mut self, enabled: bool) {
+        self.state.borrow_mut().verify_remote_moves = enabled;
+    }
+
+    /// Play a move for the local player without selecting squares, see `GameState::try_move`.
+    pub fn try_move(
+        &mut self,
+        source: Position,
+        dest: Position,
+        promotion: Option<PieceKind>,
+    ) -> Result<MoveOutcome, MoveError> {
+        self.state.borrow_mut().try_move(source, dest, promotion)
+    }
+
+    /// Start receiving the events of the game, see `GameState::subscribe`.
+    pub fn subscribe(&mut self) -> mpsc::Receiver<GameEvent> {
+        self.state.borrow_mut().subscribe()
+    }
+
+    /// Set whether destinations which would give check are highlighted in a separate color, which
+    /// costs a move simulation per destination whenever a piece is selected. Enabled by default.
+    pub fn set_highlight_checking_moves(&mut self, enabled: bool) {
+        self.state.borrow_mut().highlight_checking_moves = enabled;
+    }
+
+    /// Set whether moves are only played once their destination is selected a second time, to
+    /// avoid misclicks. Disabled by default.
+    pub fn set_confirm_moves(&mut self, enabled: bool) {
+        self.state.borrow_mut().confirm_moves = enabled;
+    }
+
+    /// Set what pawns are promoted to when moved by selecting squares, e.g. always a queen. `None`
+    /// (the default) lets the player choose each time.
+    pub fn set_auto_promote(&mut self, kind: Option<PieceKind>) {
+        self.state.borrow_mut().auto_promote = kind;
+    }
+
+    /// Discard the move waiting for confirmation, see `set_confirm_moves`. Returns whether there
+    /// was one.
+    pub fn cancel_staged_move(&mut self) -> bool {
+        self.state.borrow_mut().cancel_staged_move()
+    }
+
+    /// Take back the last move of a local game, see `GameState::undo_move`.
+    pub fn undo_move(&mut self) -> bool {
+        self.state.borrow_mut().undo_move()
     }
 
-    pub fn quit_event(&mut self) -> anyhow::Result<()> {
+    /// Offer a draw or accept the opponent's offer, see `GameState::offer_or_accept_draw`.
+    pub fn offer_or_accept_draw(&mut self) -> bool {
+        self.state.borrow_mut().offer_or_accept_draw()
+    }
+
+    /// The draw rule which the side to move may claim a draw by, see `GameState::claimable_draw`.
+    pub fn claimable_draw(&self) -> Option<DrawReason> {
+        self.state.borrow().claimable_draw()
+    }
+
+    /// Resign for the side to move once confirmed, see `GameState::request_resignation`.
+    pub fn request_resignation(&mut self) -> bool {
+        self.state.borrow_mut().request_resignation()
+    }
+
+    /// Returns who won the game and why, see `GameState::result`.
+    pub fn result(&self) -> Option<GameOutcome> {
+        self.state.borrow().result()
+    }
+
+    /// Whether closing the window should be confirmed first, i.e. if it would abandon an ongoing
+    /// networked game.
+    pub fn requires_quit_confirmation(&self) -> bool {
+        let state = self.state.borrow();
+        matches!(state.connection, network::GameConnection::Remote(..))
+            && matches!(state.game_phase, chesstp::GamePhase::Ongoing)
+    }
+
+    /// Tell the remote that the game is being left, giving `reason` which is shown to them. An
+    /// empty reason is sent as a plain resignation.
+    pub fn quit_event(&mut self, reason: &str) -> Result<(), network::TransportError> {
         match self.state.borrow_mut().connection {
-            network::GameConnection::Local => {}
+            network::GameConnection::Local | network::GameConnection::Analysis => {}
             network::GameConnection::Remote(_, _, ref mut chesstp_message_stream) => {
+                let reason = reason.trim();
                 let message = chesstp::QuitMessage {
-                    message: "User exited".to_owned(),
+                    message: if reason.is_empty() {
+                        EndReason::Resignation.quit_message().to_owned()
+                    } else {
+                        reason.to_owned()
+                    },
                 };
 
                 chesstp_message_stream.write(chesstp::Message::Quit(message))?;
@@ -985,13 +3501,67 @@ impl GameUi {
         Ok(())
     }
 
-    pub fn size() -> glam::Vec2 {
-        /// If I don't add this to the height the text at the bottom is cut off slightly, and I
-        /// can't be bothered to fix it.
-        static MAGIC_EXTRA_HEIGHT: f32 = 20.0;
-        glam::vec2(
-            800.0 + 300.0,
-            800.0 + BOARD_Y_MARGIN * 2.0 + MAGIC_EXTRA_HEIGHT,
-        )
+    /// Set whether a thin frame is drawn around the board to separate it from the background.
+    /// Enabled by default.
+    pub fn set_board_frame(&mut self, enabled: bool) {
+        self.board_frame = enabled;
+    }
+
+    pub fn set_side_bar_width(&mut self, width: f32) {
+        self.side_bar_width = width;
+    }
+
+    /// Set whether the sidebar is hidden to fit smaller screens, showing whose turn it is as a thin
+    /// bar below the board instead. Changes `size`. Disabled by default.
+    pub fn set_compact(&mut self, enabled: bool) {
+        self.compact = enabled;
+    }
+
+    pub fn compact(&self) -> bool {
+        self.compact
+    }
+
+    /// Set whether pieces are hidden while moves and rules still work as usual, for practicing
+    /// visualization. The square labels stay visible. Since it would be an unfair handicap, it
+    /// can't be enabled in networked games. Disabled by default.
+    pub fn set_blindfold(&mut self, enabled: bool) {
+        let remote = matches!(
+            self.state.borrow().connection,
+            network::GameConnection::Remote(..)
+        );
+        self.blindfold = enabled && !remote;
+    }
+
+    pub fn blindfold(&self) -> bool {
+        self.blindfold
+    }
+
+    /// How many draw calls the pieces take per frame, batched and unbatched, for the performance
+    /// overlay. Is zero while they're hidden.
+    pub fn piece_draw_calls(&self) -> (usize, usize) {
+        if self.blindfold && !self.revealed {
+            return (0, 0);
+        }
+        piece_draw_calls(&self.state.borrow().board)
+    }
+
+    /// Set whether the pieces are shown despite blindfold mode, e.g. while a key is held.
+    pub fn set_revealed(&mut self, revealed: bool) {
+        self.revealed = revealed;
+    }
+
+    /// The window size required to fit the UI. The width only covers the board and sidebar (unless
+    /// compact), while the height extends from the top of the window to the bottom of the player
+    /// label below the board, or of the turn bar when compact.
+    pub fn size(&self) -> glam::Vec2 {
+        let label_bottom = self.board_bounds.bottom() + BOTTOM_LABEL_GAP + self.bottom_label_height;
+        if self.compact {
+            glam::vec2(
+                self.board_bounds.w,
+                label_bottom + TURN_BAR_GAP + TURN_BAR_HEIGHT,
+            )
+        } else {
+            glam::vec2(self.board_bounds.w + self.side_bar_width, label_bottom)
+        }
     }
 }
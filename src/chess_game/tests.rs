@@ -0,0 +1,1257 @@
+use super::*;
+
+/// Create board from the "piece placement data" part of a FEN string.
+fn board_from_fen(placement: &str) -> BoardWrapper {
+    let mut game = chess::game::game_state::new();
+    placement
+        .parse::<chesstp::Board>()
+        .unwrap()
+        .update_game(&mut game);
+
+    BoardWrapper::new(game)
+}
+
+fn square(string: &str) -> Position {
+    Position::parse(string).unwrap()
+}
+
+#[test]
+fn position_all() {
+    let all = Position::all().collect::<Vec<_>>();
+
+    assert_eq!(all.len(), 64);
+    assert_eq!(all[0], square("a1"));
+    assert_eq!(all[1], square("b1"));
+    assert_eq!(all[63], square("h8"));
+}
+
+#[test]
+fn attacked_squares_rook_and_king() {
+    let board = board_from_fen("7k/8/8/8/8/8/8/R3K3");
+    let attacked = board.attacked_squares(Color::White);
+
+    for string in ["a2", "a8", "b1", "d1", "e1", "d2", "e2", "f2", "f1"] {
+        assert!(attacked.contains(&square(string)), "{} is attacked", string);
+    }
+    // The king blocks the rook.
+    assert!(!attacked.contains(&square("g1")));
+    assert!(!attacked.contains(&square("h1")));
+    assert!(!attacked.contains(&square("b2")));
+}
+
+#[test]
+fn attacked_squares_pawns() {
+    let board = board_from_fen("4k3/3p4/8/8/8/8/4P3/4K3");
+
+    let white = board.attacked_squares(Color::White);
+    assert!(white.contains(&square("d3")));
+    assert!(white.contains(&square("f3")));
+    assert!(!white.contains(&square("e3")));
+
+    let black = board.attacked_squares(Color::Black);
+    assert!(black.contains(&square("c6")));
+    assert!(black.contains(&square("e6")));
+    assert!(!black.contains(&square("d6")));
+}
+
+#[test]
+fn format_move_duration_seconds() {
+    assert_eq!(format_move_duration(Duration::from_millis(0)), "0.0s");
+    assert_eq!(format_move_duration(Duration::from_millis(3240)), "3.2s");
+    assert_eq!(format_move_duration(Duration::from_millis(59_900)), "59.9s");
+    assert_eq!(format_move_duration(Duration::from_millis(59_949)), "59.9s");
+}
+
+#[test]
+fn format_move_duration_rounds_up_to_minutes() {
+    assert_eq!(
+        format_move_duration(Duration::from_millis(59_950)),
+        "1m 00s"
+    );
+    assert_eq!(
+        format_move_duration(Duration::from_millis(59_990)),
+        "1m 00s"
+    );
+}
+
+#[test]
+fn format_move_duration_minutes() {
+    assert_eq!(format_move_duration(Duration::from_secs(60)), "1m 00s");
+    assert_eq!(format_move_duration(Duration::from_secs(65)), "1m 05s");
+    assert_eq!(
+        format_move_duration(Duration::from_secs(3600 + 1)),
+        "60m 01s"
+    );
+}
+
+#[test]
+fn verify_move_matching_board() {
+    let board = board_from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR");
+    let received = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR"
+        .parse::<chesstp::Board>()
+        .unwrap();
+
+    assert_eq!(
+        board.verify_move(square("e2"), square("e4"), None, Color::White, &received),
+        Ok(())
+    );
+}
+
+#[test]
+fn verify_move_desynced_board() {
+    let board = board_from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR");
+    let received = "rnbqkbnr/pppppppp/8/8/3P4/8/PPPP1PPP/RNBQKBNR"
+        .parse::<chesstp::Board>()
+        .unwrap();
+
+    assert!(
+        board
+            .verify_move(square("e2"), square("e4"), None, Color::White, &received)
+            .is_err()
+    );
+}
+
+#[test]
+fn waiting_dot_count_cycles() {
+    assert_eq!(waiting_dot_count(Duration::ZERO), 0);
+    assert_eq!(waiting_dot_count(Duration::from_millis(399)), 0);
+    assert_eq!(waiting_dot_count(Duration::from_millis(400)), 1);
+    assert_eq!(waiting_dot_count(Duration::from_millis(1200)), 3);
+    assert_eq!(waiting_dot_count(Duration::from_millis(1600)), 0);
+}
+
+#[test]
+fn side_bar_layout_stacks_areas() {
+    let board_bounds = graphics::Rect::new(10.0, 50.0, 800.0, 800.0);
+    let layout = SideBarLayout::new(board_bounds, 300.0);
+
+    assert_eq!(layout.turn_area.top(), board_bounds.top() + 30.0);
+    assert_eq!(layout.move_list_area.top(), board_bounds.top() + 170.0);
+    assert_eq!(layout.move_list_area.bottom(), layout.chat_area.top());
+    assert_eq!(layout.chat_area.bottom(), layout.action_area.top());
+    // The action buttons are kept apart from the warnings below them.
+    assert_eq!(layout.action_area.bottom(), layout.status_area.top() - 30.0);
+    assert_eq!(layout.action_area.h, 50.0);
+    assert_eq!(layout.status_area.top(), board_bounds.bottom() - 150.0);
+    assert_eq!(layout.connection_area.bottom(), board_bounds.bottom());
+    assert_eq!(layout.connection_area.left(), board_bounds.right());
+    assert_eq!(layout.connection_area.w, 300.0);
+}
+
+#[test]
+fn gives_check_rook() {
+    let board = board_from_fen("4k3/8/8/8/8/8/8/R6K");
+
+    assert!(board.gives_check(square("a1"), square("a8")));
+    assert!(!board.gives_check(square("a1"), square("a2")));
+}
+
+#[test]
+fn is_en_passant_diagonal_to_empty() {
+    let board = board_from_fen("4k3/8/8/3pP3/8/8/8/4K3");
+
+    assert!(board.is_en_passant(square("e5"), square("d6")));
+    assert!(!board.is_en_passant(square("e5"), square("e6")));
+    assert!(!board.is_en_passant(square("e1"), square("d2")));
+}
+
+#[test]
+fn set_piece_replaces_and_clears() {
+    let mut board = board_from_fen("7k/8/8/8/8/8/8/R3K3");
+    let knight = Piece {
+        kind: PieceKind::Knight,
+        color: Color::White,
+    };
+
+    board.set_piece(square("a1"), Some(knight));
+    board.set_piece(square("h8"), None);
+
+    assert_eq!(board.at(square("a1")), Some(knight));
+    assert_eq!(board.at(square("h8")), None);
+}
+
+#[test]
+fn end_reason_quit_message_round_trip() {
+    for reason in [EndReason::Resignation, EndReason::Timeout] {
+        assert_eq!(
+            EndReason::from_quit_message(reason.quit_message()),
+            Some(reason)
+        );
+    }
+    assert_eq!(
+        EndReason::from_quit_message(" timeout\n"),
+        Some(EndReason::Timeout)
+    );
+    assert_eq!(EndReason::from_quit_message("User exited"), None);
+}
+
+#[test]
+fn has_legal_moves_stalemate() {
+    // Black king cornered by a queen without being in check.
+    let board = board_from_fen("k7/2Q5/1K6/8/8/8/8/8");
+
+    assert!(!board.has_legal_moves(Color::Black));
+    assert!(board.has_legal_moves(Color::White));
+}
+
+#[test]
+fn knight_destination_markers_flipped_board() {
+    let mut board = board_from_fen("4k3/8/8/8/8/8/8/1N2K3");
+    board.set_turn(Color::White);
+    let board_bounds = graphics::Rect::new(0.0, 40.0, 800.0, 800.0);
+    let dests: HashSet<_> = board.valid_moves(square("b1")).collect();
+
+    assert_eq!(
+        dests,
+        HashSet::from([square("a3"), square("c3"), square("d2")])
+    );
+
+    // With black at the bottom the first rank is the top row, and the files are reversed.
+    for (dest, column, row) in [("a3", 7.0, 2.0), ("c3", 5.0, 2.0), ("d2", 4.0, 1.0)] {
+        let bounds = square_bounds(board_bounds, square(dest), Orientation::BlackAtBottom);
+        let center: glam::Vec2 = bounds.center().into();
+        assert_eq!(
+            center,
+            glam::vec2(column * 100.0 + 50.0, 40.0 + row * 100.0 + 50.0)
+        );
+
+        let (ring_width, ring_radius, dot_radius) = destination_marker_sizes(bounds.w, false);
+        assert_eq!(ring_radius + ring_width / 2.0, bounds.w / 2.0);
+        assert!(dot_radius < ring_radius);
+    }
+}
+
+#[test]
+fn destination_marker_sizes_scale_with_square() {
+    let small = destination_marker_sizes(50.0, false);
+    let large = destination_marker_sizes(100.0, false);
+
+    assert_eq!((small.0 * 2.0, small.1 * 2.0, small.2 * 2.0), large);
+}
+
+#[test]
+fn subscribe_receives_move_made() {
+    let mut state = GameState::new(
+        board_from_fen("4k3/8/8/8/8/8/4P3/4K3"),
+        network::GameConnection::Local,
+    );
+    let events = state.subscribe();
+
+    let outcome = state.try_move(square("e2"), square("e4"), None).unwrap();
+
+    assert_eq!(
+        events.try_recv(),
+        Ok(GameEvent::MoveMade {
+            source: square("e2"),
+            dest: square("e4"),
+            outcome,
+        })
+    );
+    assert!(events.try_recv().is_err());
+}
+
+/// Create the client (white) and server (black) side of a game connected over loopback, both
+/// starting from the piece placement `position`.
+fn connected_games(position: &str) -> (GameState, GameState) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let client = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (server, server_addr) = listener.accept().unwrap();
+    let client_addr = client.peer_addr().unwrap();
+
+    let white = GameState::new(
+        board_from_fen(position),
+        network::GameConnection::Remote(
+            network::ConnectionType::Client,
+            client_addr,
+            network::ChesstpMessageStream::new(client).unwrap(),
+        ),
+    );
+    let black = GameState::new(
+        board_from_fen(position),
+        network::GameConnection::Remote(
+            network::ConnectionType::Server,
+            server_addr,
+            network::ChesstpMessageStream::new(server).unwrap(),
+        ),
+    );
+    (white, black)
+}
+
+/// Update `state` until `done` holds for it, giving up after two seconds so that a lost message
+/// fails the test's assertions instead of hanging it.
+fn pump_until(state: &mut GameState, done: impl Fn(&GameState) -> bool) {
+    let started_at = Instant::now();
+    while !done(state) && started_at.elapsed() < Duration::from_secs(2) {
+        state.update();
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn promotion_applied_across_connection() {
+    let (mut white, mut black) = connected_games("4k3/P7/8/8/8/8/8/4K3");
+
+    white
+        .try_move(square("a7"), square("a8"), Some(PieceKind::Knight))
+        .unwrap();
+
+    pump_until(&mut black, |state| !state.moves.is_empty());
+
+    let knight = Some(Piece {
+        kind: PieceKind::Knight,
+        color: Color::White,
+    });
+    assert_eq!(white.board.at(square("a8")), knight);
+    assert_eq!(black.board.at(square("a8")), knight);
+    assert!(!black.desync_detected);
+}
+
+#[test]
+fn opponent_left_text_reasons() {
+    assert_eq!(opponent_left_text(None), "Opponent left");
+    assert_eq!(opponent_left_text(Some("  ")), "Opponent left");
+    assert_eq!(opponent_left_text(Some("Resignation")), "Opponent left");
+    assert_eq!(
+        opponent_left_text(Some("went to lunch")),
+        "Opponent left: went to lunch"
+    );
+}
+
+#[test]
+fn elide_to_fit_long_name() {
+    // Every character is 10 wide.
+    let width_of = |text: &str| Ok(text.chars().count() as f32 * 10.0);
+
+    assert_eq!(elide_to_fit("White", 50.0, width_of).unwrap(), "White");
+    assert_eq!(
+        elide_to_fit("Magnus Carlsen the Third (you)", 100.0, width_of).unwrap(),
+        "Magnus Ca…"
+    );
+    // Whitespace before the ellipsis is dropped.
+    assert_eq!(elide_to_fit("ab cd", 40.0, width_of).unwrap(), "ab…");
+    assert_eq!(elide_to_fit("White", 5.0, width_of).unwrap(), "…");
+}
+
+#[test]
+fn at_str_and_valid_moves_str() {
+    let board = board_from_fen("4k3/8/8/8/8/8/4P3/4K3");
+
+    assert_eq!(
+        board.at_str("e2"),
+        Some(Piece {
+            kind: PieceKind::Pawn,
+            color: Color::White,
+        })
+    );
+    assert_eq!(board.at_str("e3"), None);
+    assert_eq!(board.at_str("z9"), None);
+
+    assert_eq!(
+        board.valid_moves_str("e2").collect::<HashSet<_>>(),
+        HashSet::from([square("e3"), square("e4")])
+    );
+    assert_eq!(board.valid_moves_str("not a square").count(), 0);
+}
+
+#[test]
+fn analysis_either_color_moves() {
+    let mut state = GameState::new(
+        board_from_fen("4k3/4p3/8/8/8/8/4P3/4K3"),
+        network::GameConnection::Analysis,
+    );
+
+    // Black moves first, then black again, then white.
+    for (source, dest) in [("e7", "e6"), ("e6", "e5"), ("e2", "e4")] {
+        state.select_square(square(source));
+        state.select_square(square(dest));
+    }
+
+    assert_eq!(state.moves.len(), 3);
+    assert_eq!(
+        state.board.at_str("e5").map(|piece| piece.color),
+        Some(Color::Black)
+    );
+    assert_eq!(
+        state.board.at_str("e4").map(|piece| piece.color),
+        Some(Color::White)
+    );
+}
+
+#[test]
+fn orientation_flipped() {
+    assert_eq!(
+        Orientation::WhiteAtBottom.flipped(),
+        Orientation::BlackAtBottom
+    );
+    assert_eq!(
+        Orientation::BlackAtBottom.flipped().flipped(),
+        Orientation::BlackAtBottom
+    );
+}
+
+#[test]
+fn set_turn_round_trip() {
+    let mut board = board_from_fen("4k3/8/8/8/8/8/8/4K3");
+    for color in [Color::White, Color::Black] {
+        board.set_turn(color);
+        assert_eq!(board.turn(), color);
+        assert_eq!(Color::from_fen_char(color.to_fen_char()), Some(color));
+    }
+    assert_eq!(Color::from_fen_char('x'), None);
+}
+
+#[test]
+fn position_file_and_rank_chars() {
+    let files: String = (0..8)
+        .map(|column| Position::new(column, 0).unwrap().file_char())
+        .collect();
+    assert_eq!(files, "abcdefgh");
+
+    let ranks: String = (0..8)
+        .map(|row| Position::new(0, row).unwrap().rank_char())
+        .collect();
+    assert_eq!(ranks, "12345678");
+
+    let position = Position::parse("g3").unwrap();
+    assert_eq!(position.to_string(false), "g3");
+    assert_eq!(position.to_string(true), "G3");
+    assert_eq!(format!("{:?}", position), "g3");
+}
+
+#[test]
+fn client_cannot_select_black_pieces() {
+    let (mut white, _black) = connected_games("4k3/4p3/8/8/8/8/4P3/4K3");
+
+    white.select_square(square("e7"));
+    assert_eq!(white.turn_phase, TurnPhase::SelectSource);
+
+    // Even if the board says black is to move without the client waiting for the remote.
+    white.board.set_turn(Color::Black);
+    assert!(!white.is_my_turn());
+    white.select_square(square("e7"));
+    assert_eq!(white.turn_phase, TurnPhase::SelectSource);
+    assert_eq!(
+        white.try_move(square("e7"), square("e6"), None),
+        Err(MoveError::WrongPlayer)
+    );
+
+    white.board.set_turn(Color::White);
+    white.select_square(square("e2"));
+    assert_eq!(white.turn_phase, TurnPhase::SelectDest(square("e2")));
+}
+
+#[test]
+fn all_legal_moves_starting_position() {
+    let board = BoardWrapper::new(chess::game::game_state::new());
+
+    let white_moves = board.all_legal_moves(Color::White);
+    assert_eq!(white_moves.len(), 20);
+    assert!(white_moves.contains(&(square("g1"), square("f3"))));
+    assert!(white_moves.contains(&(square("e2"), square("e4"))));
+}
+
+#[test]
+fn perft_starting_position() {
+    let board = BoardWrapper::new(chess::game::game_state::new());
+
+    assert_eq!(board.perft(0), 1);
+    assert_eq!(board.perft(1), 20);
+    assert_eq!(board.perft(2), 400);
+}
+
+#[test]
+fn turn_flash_alpha_fades() {
+    assert_eq!(turn_flash_alpha(Duration::ZERO), 1.0);
+    assert_eq!(turn_flash_alpha(TURN_FLASH_DURATION / 2), 0.5);
+    assert_eq!(turn_flash_alpha(TURN_FLASH_DURATION), 0.0);
+    assert_eq!(turn_flash_alpha(TURN_FLASH_DURATION * 3), 0.0);
+}
+
+#[test]
+fn piece_draw_calls_batched_per_kind_and_color() {
+    // The starting position draws 32 pieces with 12 batches.
+    assert_eq!(
+        piece_draw_calls(&board_from_fen(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR"
+        )),
+        (12, 32)
+    );
+    assert_eq!(
+        piece_draw_calls(&board_from_fen("4k3/8/8/8/8/8/8/4K3")),
+        (2, 2)
+    );
+    assert_eq!(
+        piece_draw_calls(&board_from_fen("4k3/pppp4/8/8/8/8/PPPP4/4K3")),
+        (4, 10)
+    );
+}
+
+#[test]
+fn flip_progress_settles() {
+    assert_eq!(flip_progress(Duration::ZERO), 0.0);
+    assert_eq!(flip_progress(FLIP_DURATION / 2), 0.5);
+    assert_eq!(flip_progress(FLIP_DURATION), 1.0);
+    assert_eq!(flip_progress(FLIP_DURATION * 3), 1.0);
+}
+
+#[test]
+fn flipping_square_bounds_from_old_to_new_orientation() {
+    let board_bounds = graphics::Rect::new(10.0, 10.0, 800.0, 800.0);
+    let new_bounds = square_bounds(board_bounds, square("a1"), Orientation::BlackAtBottom);
+    let old_bounds = square_bounds(board_bounds, square("a1"), Orientation::WhiteAtBottom);
+
+    let start = flipping_square_bounds(board_bounds, new_bounds, 0.0);
+    assert!((start.x - old_bounds.x).abs() < 0.01, "{:?}", start);
+    assert!((start.y - old_bounds.y).abs() < 0.01, "{:?}", start);
+    assert_eq!(start.size(), old_bounds.size());
+
+    assert_eq!(
+        flipping_square_bounds(board_bounds, new_bounds, 1.0),
+        new_bounds
+    );
+}
+
+#[test]
+fn turn_flash_only_after_received_move() {
+    let (mut white, mut black) = connected_games("4k3/4p3/8/8/8/8/4P3/4K3");
+    assert_eq!(white.turn_flash_started_at, None);
+
+    white.try_move(square("e2"), square("e4"), None).unwrap();
+
+    pump_until(&mut black, |state| !state.moves.is_empty());
+
+    assert!(black.turn_flash_started_at.is_some());
+    assert_eq!(white.turn_flash_started_at, None);
+}
+
+#[test]
+fn material_with_piece_values() {
+    let board = board_from_fen("4k3/8/8/8/8/8/PPB5/4K3");
+    assert_eq!(board.material(Color::White, &PieceValues::default()), 5.0);
+    assert_eq!(board.material(Color::Black, &PieceValues::default()), 0.0);
+
+    let values = PieceValues::new(1.0, 3.0, 3.25, 5.0, 9.0).unwrap();
+    assert_eq!(board.material(Color::White, &values), 5.25);
+
+    assert!(PieceValues::new(1.0, -3.0, 3.0, 5.0, 9.0).is_err());
+    assert!(PieceValues::new(f32::NAN, 3.0, 3.0, 5.0, 9.0).is_err());
+}
+
+#[test]
+fn confirm_moves_stages_until_selected_again() {
+    let mut state = GameState::new(
+        board_from_fen("4k3/4p3/8/8/8/8/4P3/4K3"),
+        network::GameConnection::Local,
+    );
+    state.confirm_moves = true;
+
+    state.select_square(square("e2"));
+    state.select_square(square("e4"));
+    assert_eq!(
+        state.turn_phase,
+        TurnPhase::ConfirmMove {
+            source: square("e2"),
+            dest: square("e4"),
+        }
+    );
+    assert!(state.board.at_str("e4").is_none());
+
+    // Selecting another square cancels.
+    state.select_square(square("e3"));
+    assert_eq!(state.turn_phase, TurnPhase::SelectSource);
+    assert!(state.moves.is_empty());
+
+    state.select_square(square("e2"));
+    state.select_square(square("e4"));
+    assert!(state.moves.is_empty());
+    state.select_square(square("e4"));
+    assert_eq!(state.moves.len(), 1);
+    assert!(state.board.at_str("e4").is_some());
+
+    assert!(!state.cancel_staged_move());
+}
+
+#[test]
+fn orientation_corner_squares() {
+    let board_bounds = graphics::Rect::new(0.0, 40.0, 800.0, 800.0);
+    let top_left = glam::vec2(0.0, 40.0);
+    let top_right = glam::vec2(700.0, 40.0);
+    let bottom_left = glam::vec2(0.0, 740.0);
+    let bottom_right = glam::vec2(700.0, 740.0);
+
+    for (orientation, a1, h8) in [
+        (Orientation::WhiteAtBottom, bottom_left, top_right),
+        (Orientation::BlackAtBottom, top_right, bottom_left),
+    ] {
+        let a1_bounds = square_bounds(board_bounds, square("a1"), orientation);
+        let h8_bounds = square_bounds(board_bounds, square("h8"), orientation);
+        assert_eq!(glam::Vec2::from(a1_bounds.point()), a1);
+        assert_eq!(glam::Vec2::from(h8_bounds.point()), h8);
+    }
+
+    let h1 = square_bounds(board_bounds, square("h1"), Orientation::BlackAtBottom);
+    assert_eq!(glam::Vec2::from(h1.point()), top_left);
+    let a8 = square_bounds(board_bounds, square("a8"), Orientation::BlackAtBottom);
+    assert_eq!(glam::Vec2::from(a8.point()), bottom_right);
+}
+
+#[test]
+fn unknown_library_pieces_are_errors() {
+    let mut board = board_from_fen("4k3/8/8/8/8/8/4P3/4K3");
+
+    // e2 is row 2 and column 5 in the library's one-based indices.
+    board.inner_mut().board[2][5] = "dragon".to_owned();
+    assert_eq!(
+        board.try_at(square("e2")),
+        Err(chesstp::TileError::InvalidPiece("dragon".to_owned()))
+    );
+    assert_eq!(board.at(square("e2")), None);
+    assert_eq!(
+        board.unknown_squares(),
+        vec![(
+            square("e2"),
+            chesstp::TileError::InvalidPiece("dragon".to_owned())
+        )]
+    );
+
+    board.inner_mut().board[2][5] = "pawn".to_owned();
+    board.inner_mut().player[2][5] = 'x';
+    assert_eq!(
+        board.try_at(square("e2")),
+        Err(chesstp::TileError::InvalidPlayer('x'))
+    );
+
+    assert_eq!(PieceKind::from_library_name("Knight"), None);
+    assert_eq!(PieceKind::from_fen_char('N'), Some(PieceKind::Knight));
+    assert_eq!(PieceKind::from_fen_char('x'), None);
+}
+
+#[test]
+fn stalemate_ends_in_draw() {
+    let mut state = GameState::new(
+        board_from_fen("k7/8/1K6/8/8/8/8/2Q5"),
+        network::GameConnection::Local,
+    );
+
+    state.try_move(square("c1"), square("c7"), None).unwrap();
+
+    assert_eq!(state.game_phase, chesstp::GamePhase::Draw);
+    assert_eq!(
+        state.end_reason,
+        Some(EndReason::Draw(DrawReason::Stalemate))
+    );
+}
+
+#[test]
+fn insufficient_material_ends_in_draw() {
+    let mut state = GameState::new(
+        board_from_fen("4k3/8/8/8/8/8/3n4/4K3"),
+        network::GameConnection::Local,
+    );
+
+    state.try_move(square("e1"), square("d2"), None).unwrap();
+
+    assert_eq!(
+        state.end_reason,
+        Some(EndReason::Draw(DrawReason::InsufficientMaterial))
+    );
+}
+
+#[test]
+fn has_insufficient_material_cases() {
+    assert!(board_from_fen("4k3/8/8/8/8/8/8/4K3").has_insufficient_material());
+    assert!(board_from_fen("4k3/8/8/8/8/8/8/2B1K3").has_insufficient_material());
+    // Bishops on squares of the same color, c1 and f8.
+    assert!(board_from_fen("4kb2/8/8/8/8/8/8/2B1K3").has_insufficient_material());
+    assert!(!board_from_fen("4k3/8/8/8/8/8/8/2BBK3").has_insufficient_material());
+    assert!(!board_from_fen("4k3/8/8/8/8/8/8/1NN1K3").has_insufficient_material());
+    assert!(!board_from_fen("4k3/8/8/8/8/8/4P3/4K3").has_insufficient_material());
+}
+
+#[test]
+fn fifty_move_rule_makes_draw_claimable() {
+    let mut state = GameState::new(
+        board_from_fen("4k3/8/8/8/8/8/8/R3K3"),
+        network::GameConnection::Local,
+    );
+    state.halfmove_clock = CLAIMABLE_MOVE_LIMIT - 1;
+
+    state.try_move(square("a1"), square("a2"), None).unwrap();
+    assert_eq!(state.end_reason, None);
+    assert_eq!(state.claimable_draw(), Some(DrawReason::FiftyMove));
+
+    assert!(state.offer_or_accept_draw());
+    assert_eq!(
+        state.end_reason,
+        Some(EndReason::Draw(DrawReason::FiftyMove))
+    );
+}
+
+#[test]
+fn seventy_five_move_rule_ends_in_draw() {
+    let mut state = GameState::new(
+        board_from_fen("4k3/8/8/8/8/8/8/R3K3"),
+        network::GameConnection::Local,
+    );
+    state.halfmove_clock = AUTOMATIC_MOVE_LIMIT - 1;
+
+    state.try_move(square("a1"), square("a2"), None).unwrap();
+
+    assert_eq!(
+        state.end_reason,
+        Some(EndReason::Draw(DrawReason::FiftyMove))
+    );
+}
+
+#[test]
+fn repetition_makes_draw_claimable_then_ends() {
+    let mut state = GameState::new(
+        board_from_fen("4k3/8/8/1r6/1R6/8/8/4K3"),
+        network::GameConnection::Local,
+    );
+
+    // The starting position occurs again every time the rooks have gone back and forth.
+    let shuffle = [("b4", "b3"), ("b5", "b6"), ("b3", "b4"), ("b6", "b5")];
+    for _ in 0..2 {
+        for (source, dest) in shuffle {
+            assert_eq!(state.claimable_draw(), None);
+            state.try_move(square(source), square(dest), None).unwrap();
+        }
+    }
+    assert_eq!(state.end_reason, None);
+    assert_eq!(state.claimable_draw(), Some(DrawReason::Repetition));
+
+    for (index, (source, dest)) in shuffle.iter().chain(&shuffle).enumerate() {
+        assert_eq!(state.end_reason, None, "ended before half move {}", index);
+        state.try_move(square(source), square(dest), None).unwrap();
+    }
+
+    assert_eq!(
+        state.end_reason,
+        Some(EndReason::Draw(DrawReason::Repetition))
+    );
+}
+
+#[test]
+fn repetition_not_counted_across_castling_rights_change() {
+    let mut state = GameState::new(
+        board_from_fen("r3k3/8/8/8/8/8/8/R3K3"),
+        network::GameConnection::Local,
+    );
+
+    // Moving the rooks off their starting squares gives up castling, so the starting position
+    // only repeats as far as the board shows.
+    let shuffle = [("a1", "a2"), ("a8", "a7"), ("a2", "a1"), ("a7", "a8")];
+    for (source, dest) in shuffle.iter().chain(&shuffle) {
+        state.try_move(square(source), square(dest), None).unwrap();
+    }
+
+    assert_eq!(state.claimable_draw(), None);
+}
+
+#[test]
+fn analysis_ignores_repetition() {
+    let mut state = GameState::new(
+        board_from_fen("4k3/8/8/1r6/1R6/8/8/4K3"),
+        network::GameConnection::Analysis,
+    );
+
+    let shuffle = [("b4", "b3"), ("b5", "b6"), ("b3", "b4"), ("b6", "b5")];
+    for _ in 0..5 {
+        for (source, dest) in shuffle {
+            state.try_move(square(source), square(dest), None).unwrap();
+        }
+    }
+
+    assert_eq!(state.game_phase, chesstp::GamePhase::Ongoing);
+    assert_eq!(state.claimable_draw(), None);
+}
+
+#[test]
+fn received_draw_without_rule_is_agreement() {
+    let position = "4k3/4p3/8/8/8/8/4P3/4K3";
+    let (mut white, mut black) = connected_games(position);
+
+    // Claim a draw along with a normal move, which no rule makes a draw.
+    let mut board = board_from_fen(position);
+    board.make_move(square("e2"), square("e4"), None).unwrap();
+    let network::GameConnection::Remote(_, _, ref mut stream) = white.connection else {
+        unreachable!("connected_games creates remote games");
+    };
+    stream
+        .write(chesstp::Message::Move(chesstp::MoveMessage {
+            source: square("e2"),
+            dest: square("e4"),
+            promotion: None,
+            phase: chesstp::GamePhase::Draw,
+            board: board.inner().clone().into(),
+        }))
+        .unwrap();
+
+    pump_until(&mut black, |state| !state.moves.is_empty());
+
+    assert_eq!(
+        black.end_reason,
+        Some(EndReason::Draw(DrawReason::Agreement))
+    );
+}
+
+#[test]
+fn local_draw_offer_accepted() {
+    let mut state = GameState::new(
+        board_from_fen("4k3/4p3/8/8/8/8/4P3/4K3"),
+        network::GameConnection::Local,
+    );
+
+    // White offers along with their move, black accepts.
+    assert!(state.offer_or_accept_draw());
+    state.try_move(square("e2"), square("e4"), None).unwrap();
+    assert_eq!(state.draw_offered_by, Some(Color::White));
+    assert!(state.offer_or_accept_draw());
+
+    assert_eq!(
+        state.end_reason,
+        Some(EndReason::Draw(DrawReason::Agreement))
+    );
+}
+
+#[test]
+fn local_draw_offer_declined_by_moving() {
+    let mut state = GameState::new(
+        board_from_fen("4k3/4p3/8/8/8/8/4P3/4K3"),
+        network::GameConnection::Local,
+    );
+
+    state.offer_or_accept_draw();
+    state.try_move(square("e2"), square("e4"), None).unwrap();
+    state.try_move(square("e7"), square("e5"), None).unwrap();
+
+    assert_eq!(state.draw_offered_by, None);
+    assert_eq!(state.end_reason, None);
+}
+
+#[test]
+fn local_resignation_needs_confirmation() {
+    let mut state = GameState::new(
+        board_from_fen("4k3/4p3/8/8/8/8/4P3/4K3"),
+        network::GameConnection::Local,
+    );
+
+    assert!(state.request_resignation());
+    assert_eq!(state.end_reason, None);
+    assert!(state.request_resignation());
+    assert_eq!(state.game_phase, chesstp::GamePhase::Win(Color::Black));
+    assert_eq!(state.end_reason, Some(EndReason::Resignation));
+
+    // A finished game can't be resigned.
+    assert!(!state.request_resignation());
+}
+
+#[test]
+fn analysis_resignation_rejected() {
+    let mut state = GameState::new(
+        board_from_fen("4k3/4p3/8/8/8/8/4P3/4K3"),
+        network::GameConnection::Analysis,
+    );
+
+    assert!(!state.request_resignation());
+    assert!(!state.request_resignation());
+    assert_eq!(state.game_phase, chesstp::GamePhase::Ongoing);
+}
+
+#[test]
+fn is_in_check_loaded_position() {
+    let mut board = board_from_fen("4k3/8/8/8/8/8/8/4R1K1");
+
+    assert!(board.is_in_check(Color::Black));
+    assert!(!board.is_in_check(Color::White));
+    // White is to move, so no king is highlighted until it's black's turn.
+    assert_eq!(board.checked_king(), None);
+
+    board.set_turn(Color::Black);
+    let state = GameState::new(board, network::GameConnection::Local);
+    assert_eq!(state.checked_king, Some(square("e8")));
+}
+
+#[test]
+fn is_in_check_blocked() {
+    let board = board_from_fen("4k3/4n3/8/8/8/8/8/4R1K1");
+
+    assert!(!board.is_in_check(Color::Black));
+}
+
+#[test]
+fn checked_king_follows_moves() {
+    let mut state = GameState::new(
+        board_from_fen("4k3/8/8/8/8/8/8/R3K3"),
+        network::GameConnection::Local,
+    );
+    assert_eq!(state.checked_king, None);
+
+    state.try_move(square("a1"), square("a8"), None).unwrap();
+    assert_eq!(state.checked_king, Some(square("e8")));
+
+    state.try_move(square("e8"), square("e7"), None).unwrap();
+    assert_eq!(state.checked_king, None);
+}
+
+#[test]
+fn undo_two_moves() {
+    let position = "4k3/4p3/8/8/8/8/4P3/4K3";
+    let mut state = GameState::new(board_from_fen(position), network::GameConnection::Local);
+
+    state.try_move(square("e2"), square("e4"), None).unwrap();
+    state.try_move(square("e7"), square("e5"), None).unwrap();
+    assert!(state.undo_move());
+    assert_eq!(state.board.at_str("e5"), None);
+    assert_eq!(state.last_move, Some((square("e2"), square("e4"))));
+    assert!(state.undo_move());
+
+    let expected = board_from_fen(position);
+    for square in Position::all() {
+        assert_eq!(state.board.at(square), expected.at(square), "{:?}", square);
+    }
+    assert_eq!(state.board.turn(), Color::White);
+    assert_eq!(state.last_move, None);
+    assert!(state.moves.is_empty());
+    assert!(!state.undo_move());
+}
+
+#[test]
+fn undo_reopens_finished_game() {
+    let mut state = GameState::new(
+        board_from_fen("k7/8/1K6/8/8/8/8/2Q5"),
+        network::GameConnection::Local,
+    );
+
+    state.try_move(square("c1"), square("c7"), None).unwrap();
+    assert_eq!(state.game_phase, chesstp::GamePhase::Draw);
+
+    assert!(state.undo_move());
+    assert_eq!(state.game_phase, chesstp::GamePhase::Ongoing);
+    assert_eq!(state.end_reason, None);
+    assert!(state.is_my_turn());
+}
+
+#[test]
+fn received_move_inconsistent_with_board_rejected() {
+    let position = "4k3/4p3/8/8/8/8/4P3/4K3";
+    let (mut white, mut black) = connected_games(position);
+
+    // Claim e2e4 while sending the board from before the move.
+    let network::GameConnection::Remote(_, _, ref mut stream) = white.connection else {
+        unreachable!("connected_games creates remote games");
+    };
+    stream
+        .write(chesstp::Message::Move(chesstp::MoveMessage {
+            source: square("e2"),
+            dest: square("e4"),
+            promotion: None,
+            phase: chesstp::GamePhase::Ongoing,
+            board: position.parse().unwrap(),
+        }))
+        .unwrap();
+
+    pump_until(&mut black, |state| state.desync_detected);
+
+    assert!(black.desync_detected);
+    assert!(black.moves.is_empty());
+    assert_eq!(black.board.at_str("e4"), None);
+}
+
+#[test]
+fn received_second_move_in_a_row_rejected() {
+    let (mut white, mut black) = connected_games("4k3/4p3/8/8/8/8/3PP3/4K3");
+
+    white.try_move(square("e2"), square("e4"), None).unwrap();
+    pump_until(&mut black, |state| !state.moves.is_empty());
+    assert_eq!(black.moves.len(), 1);
+
+    // Send another white move, although it's black's turn.
+    deliver_move(
+        &mut white,
+        &mut black,
+        chesstp::MoveMessage {
+            source: square("d2"),
+            dest: square("d4"),
+            promotion: None,
+            phase: chesstp::GamePhase::Ongoing,
+            board: "4k3/4p3/8/8/3PP3/8/8/4K3".parse().unwrap(),
+        },
+    );
+    pump_until(&mut black, |state| state.desync_detected);
+
+    assert!(black.desync_detected);
+    assert_eq!(black.moves.len(), 1);
+    assert!(black.board.at_str("d2").is_some());
+    assert_eq!(black.board.at_str("d4"), None);
+}
+
+#[test]
+fn received_board_disagreeing_with_move_rejected() {
+    let position = "4k3/4p3/8/8/8/8/4P3/4K3";
+    let (mut white, mut black) = connected_games(position);
+
+    // Play e2e4, but send a board which also has a queen appearing on d1.
+    let network::GameConnection::Remote(_, _, ref mut stream) = white.connection else {
+        unreachable!("connected_games creates remote games");
+    };
+    stream
+        .write(chesstp::Message::Move(chesstp::MoveMessage {
+            source: square("e2"),
+            dest: square("e4"),
+            promotion: None,
+            phase: chesstp::GamePhase::Ongoing,
+            board: "4k3/4p3/8/8/4P3/8/8/3QK3".parse().unwrap(),
+        }))
+        .unwrap();
+
+    pump_until(&mut black, |state| state.desync_detected);
+
+    assert!(black.desync_detected);
+    assert!(black.moves.is_empty());
+    assert_eq!(black.board.at_str("d1"), None);
+    assert!(black.board.at_str("e2").is_some());
+}
+
+#[test]
+fn result_of_finished_games() {
+    let mut state = GameState::new(
+        board_from_fen("k7/8/1K6/8/8/8/8/2Q5"),
+        network::GameConnection::Local,
+    );
+    assert_eq!(state.result(), None);
+
+    state.try_move(square("c1"), square("c7"), None).unwrap();
+    assert_eq!(
+        state.result(),
+        Some(GameOutcome {
+            winner: None,
+            reason: EndReason::Draw(DrawReason::Stalemate),
+        })
+    );
+
+    let mut state = GameState::new(
+        board_from_fen("4k3/4p3/8/8/8/8/4P3/4K3"),
+        network::GameConnection::Local,
+    );
+    state.request_resignation();
+    state.request_resignation();
+    assert_eq!(
+        state.result(),
+        Some(GameOutcome {
+            winner: Some(Color::Black),
+            reason: EndReason::Resignation,
+        })
+    );
+}
+
+#[test]
+fn auto_promote_selected_move() {
+    for kind in [PieceKind::Queen, PieceKind::Knight] {
+        let mut state = GameState::new(
+            board_from_fen("4k3/P7/8/8/8/8/8/4K3"),
+            network::GameConnection::Local,
+        );
+        state.auto_promote = Some(kind);
+
+        state.select_square(square("a7"));
+        state.select_square(square("a8"));
+
+        assert_eq!(
+            state.board.at_str("a8"),
+            Some(Piece {
+                kind,
+                color: Color::White,
+            })
+        );
+    }
+}
+
+#[test]
+fn choose_promotion_selected_move() {
+    let mut state = GameState::new(
+        board_from_fen("k7/4P3/8/8/8/8/8/4K3"),
+        network::GameConnection::Local,
+    );
+
+    state.select_square(square("e7"));
+    state.select_square(square("e8"));
+    assert_eq!(
+        state.turn_phase,
+        TurnPhase::ChoosePromotion {
+            source: square("e7"),
+            dest: square("e8"),
+        }
+    );
+
+    // The board is blocked until a piece is chosen.
+    state.select_square(square("e1"));
+    assert!(state.board.at_str("e8").is_none());
+
+    assert!(state.choose_promotion(PieceKind::Rook));
+    assert_eq!(
+        state.board.at_str("e8"),
+        Some(Piece {
+            kind: PieceKind::Rook,
+            color: Color::White,
+        })
+    );
+    assert_eq!(state.board.turn(), Color::Black);
+    assert!(!state.choose_promotion(PieceKind::Queen));
+}
+
+#[test]
+fn cancel_promotion_choice() {
+    let mut state = GameState::new(
+        board_from_fen("k7/4P3/8/8/8/8/8/4K3"),
+        network::GameConnection::Local,
+    );
+
+    state.select_square(square("e7"));
+    state.select_square(square("e8"));
+    assert!(state.cancel_staged_move());
+
+    assert_eq!(state.turn_phase, TurnPhase::SelectSource);
+    assert!(state.board.at_str("e8").is_none());
+    assert_eq!(state.board.turn(), Color::White);
+}
+
+/// Send `message` from `sender` as is, and update `receiver` until it has either played it or
+/// rejected it.
+fn deliver_move(sender: &mut GameState, receiver: &mut GameState, message: chesstp::MoveMessage) {
+    let network::GameConnection::Remote(_, _, ref mut stream) = sender.connection else {
+        unreachable!("connected_games creates remote games");
+    };
+    stream.write(chesstp::Message::Move(message)).unwrap();
+
+    pump_until(receiver, |state| {
+        !state.moves.is_empty() || state.desync_detected
+    });
+}
+
+#[test]
+fn received_promotion_to_other_piece_rejected() {
+    let (mut white, mut black) = connected_games("4k3/P7/8/8/8/8/8/4K3");
+
+    // Claim a knight promotion while sending a board with a queen.
+    deliver_move(
+        &mut white,
+        &mut black,
+        chesstp::MoveMessage {
+            source: square("a7"),
+            dest: square("a8"),
+            promotion: Some(PieceKind::Knight),
+            phase: chesstp::GamePhase::Ongoing,
+            board: "Q3k3/8/8/8/8/8/8/4K3".parse().unwrap(),
+        },
+    );
+
+    assert!(black.desync_detected);
+    assert!(black.moves.is_empty());
+    assert_eq!(black.board.at_str("a8"), None);
+}
+
+#[test]
+fn received_promotion_of_non_pawn_rejected() {
+    let (mut white, mut black) = connected_games("4k3/8/8/8/8/8/8/1N2K3");
+
+    deliver_move(
+        &mut white,
+        &mut black,
+        chesstp::MoveMessage {
+            source: square("b1"),
+            dest: square("c3"),
+            promotion: Some(PieceKind::Queen),
+            phase: chesstp::GamePhase::Ongoing,
+            board: "4k3/8/8/8/8/2N5/8/4K3".parse().unwrap(),
+        },
+    );
+
+    assert!(black.desync_detected);
+    assert!(black.moves.is_empty());
+    assert_eq!(
+        black.board.at_str("b1"),
+        Some(Piece {
+            kind: PieceKind::Knight,
+            color: Color::White,
+        })
+    );
+}
+
+#[test]
+fn failed_move_send_reported_by_health() {
+    let (mut white, _black) = connected_games("4k3/4p3/8/8/8/8/4P3/4K3");
+    let network::GameConnection::Remote(_, _, ref mut stream) = white.connection else {
+        unreachable!("connected_games creates remote games");
+    };
+    stream.close().unwrap();
+
+    assert!(white.try_move(square("e2"), square("e4"), None).is_ok());
+
+    let network::GameConnection::Remote(_, _, ref stream) = white.connection else {
+        unreachable!("connected_games creates remote games");
+    };
+    assert_eq!(stream.health(), network::ConnectionHealth::WriteFailed);
+}
+
+#[test]
+fn remote_resignation() {
+    let (mut white, mut black) = connected_games("4k3/4p3/8/8/8/8/4P3/4K3");
+
+    assert!(white.resign());
+    assert_eq!(white.game_phase, chesstp::GamePhase::Win(Color::Black));
+    assert_eq!(
+        white.try_move(square("e2"), square("e4"), None),
+        Err(MoveError::GameOver)
+    );
+
+    pump_until(&mut black, |state| state.end_reason.is_some());
+
+    assert_eq!(
+        black.result(),
+        Some(GameOutcome {
+            winner: Some(Color::Black),
+            reason: EndReason::Resignation,
+        })
+    );
+    assert!(!white.resign());
+}
+
+#[test]
+fn knight_promotion_mates() {
+    // Only a knight on f8 reaches the king, which its own pieces box in.
+    let mut state = GameState::new(
+        board_from_fen("6br/5Ppk/6pp/8/8/8/8/K7"),
+        network::GameConnection::Local,
+    );
+
+    let outcome = state
+        .try_move(square("f7"), square("f8"), Some(PieceKind::Knight))
+        .unwrap();
+
+    assert_eq!(outcome, MoveOutcome::Checkmate);
+    assert_eq!(
+        state.result(),
+        Some(GameOutcome {
+            winner: Some(Color::White),
+            reason: EndReason::Checkmate,
+        })
+    );
+}
+
+#[test]
+fn knight_promotion_misses_queen_mate() {
+    // A queen on a8 would mate along the back rank.
+    let mut state = GameState::new(
+        board_from_fen("7k/P5pp/8/8/8/8/8/K7"),
+        network::GameConnection::Local,
+    );
+
+    let outcome = state
+        .try_move(square("a7"), square("a8"), Some(PieceKind::Knight))
+        .unwrap();
+
+    assert_eq!(outcome, MoveOutcome::Valid);
+    assert_eq!(state.game_phase, chesstp::GamePhase::Ongoing);
+}
@@ -54,34 +54,103 @@ impl BorderRadii {
     }
 }
 
+/// A tessellation tolerance for a circle (or arc) of the specified radius, i.e. how far in pixels
+/// the generated polygon may deviate from the true curve. Grows with the radius so that small
+/// circles don't get hundreds of needless vertices, while staying well below a pixel.
+pub fn default_tolerance(radius: f32) -> f32 {
+    (radius * 0.005).clamp(0.05, 0.25)
+}
+
+/// Multiply two colors component-wise, which is how a mesh's vertex color is tinted by the color of
+/// its `DrawParam`.
+fn multiply_colors(a: graphics::Color, b: graphics::Color) -> graphics::Color {
+    graphics::Color::new(a.r * b.r, a.g * b.g, a.b * b.b, a.a * b.a)
+}
+
+/// Create a rectangle filling `bounds` which fades from `top` at the top edge to `bottom` at the
+/// bottom edge, interpolated by the GPU across a single quad.
+pub fn vertical_gradient(
+    gfx: &impl context::Has<graphics::GraphicsContext>,
+    bounds: graphics::Rect,
+    top: graphics::Color,
+    bottom: graphics::Color,
+) -> graphics::Mesh {
+    let vertex = |x: f32, y: f32, color: graphics::Color| graphics::Vertex {
+        position: [x, y],
+        uv: [0.0, 0.0],
+        color: color.into(),
+    };
+
+    graphics::Mesh::from_data(
+        gfx,
+        graphics::MeshData {
+            vertices: &[
+                vertex(bounds.left(), bounds.top(), top),
+                vertex(bounds.right(), bounds.top(), top),
+                vertex(bounds.right(), bounds.bottom(), bottom),
+                vertex(bounds.left(), bounds.bottom(), bottom),
+            ],
+            indices: &[0, 1, 2, 0, 2, 3],
+        },
+    )
+}
+
+/// Rectangle with individually rounded corners. The meshes are tessellated in white and tinted
+/// when drawn, so `set_color` is cheap and the same rectangle can be reused across frames, moved
+/// with `DrawParam::dest`.
 pub struct RoundedRectangle {
     bounds: graphics::Rect,
+    color: graphics::Color,
     meshes: [graphics::Mesh; 5],
 }
 
 impl RoundedRectangle {
+    /// Create rounded rectangle, tessellating the corners with `default_tolerance` of the largest
+    /// corner radius.
     pub fn new(
         gfx: &impl context::Has<graphics::GraphicsContext>,
         mode: graphics::DrawMode,
         bounds: graphics::Rect,
         corners: BorderRadii,
         color: graphics::Color,
+    ) -> GameResult<Self> {
+        let largest_radius = corners
+            .top_left
+            .max(corners.top_right)
+            .max(corners.bottom_left)
+            .max(corners.bottom_right);
+
+        Self::with_tolerance(
+            gfx,
+            mode,
+            bounds,
+            corners,
+            color,
+            default_tolerance(largest_radius),
+        )
+    }
+
+    /// Like `RoundedRectangle::new`, but with the specified tessellation tolerance for the corners.
+    pub fn with_tolerance(
+        gfx: &impl context::Has<graphics::GraphicsContext>,
+        mode: graphics::DrawMode,
+        bounds: graphics::Rect,
+        corners: BorderRadii,
+        color: graphics::Color,
+        tolerance: f32,
     ) -> GameResult<Self> {
         let top_left = glam::vec2(bounds.left(), bounds.top());
         let top_right = glam::vec2(bounds.right(), bounds.top());
         let bottom_left = glam::vec2(bounds.left(), bounds.bottom());
         let bottom_right = glam::vec2(bounds.right(), bounds.bottom());
 
-        // TODO: Figure out tolerance
-        let tolerance = 0.001;
-
         let top_left_corner = graphics::Mesh::new_circle(
             gfx,
             mode,
             top_left + glam::vec2(corners.top_left, corners.top_left),
             corners.top_left,
             tolerance,
-            color,
+            graphics::Color::WHITE,
         )?;
         let top_right_corner = graphics::Mesh::new_circle(
             gfx,
@@ -89,7 +158,7 @@ impl RoundedRectangle {
             top_right + glam::vec2(-corners.top_right, corners.top_right),
             corners.top_right,
             tolerance,
-            color,
+            graphics::Color::WHITE,
         )?;
         let bottom_left_corner = graphics::Mesh::new_circle(
             gfx,
@@ -97,7 +166,7 @@ impl RoundedRectangle {
             bottom_left + glam::vec2(corners.bottom_left, -corners.bottom_left),
             corners.bottom_left,
             tolerance,
-            color,
+            graphics::Color::WHITE,
         )?;
         let bottom_right_corner = graphics::Mesh::new_circle(
             gfx,
@@ -105,7 +174,7 @@ impl RoundedRectangle {
             bottom_right + glam::vec2(-corners.bottom_right, -corners.bottom_right),
             corners.bottom_right,
             tolerance,
-            color,
+            graphics::Color::WHITE,
         )?;
 
         let center = graphics::Mesh::new_polygon(
@@ -125,11 +194,12 @@ impl RoundedRectangle {
                 top_left + glam::vec2(0.0, corners.top_left),
                 top_left + glam::vec2(corners.top_left, corners.top_left),
             ],
-            color,
+            graphics::Color::WHITE,
         )?;
 
         Ok(Self {
             bounds,
+            color,
             meshes: [
                 top_left_corner,
                 top_right_corner,
@@ -139,6 +209,15 @@ impl RoundedRectangle {
             ],
         })
     }
+
+    pub fn color(&self) -> graphics::Color {
+        self.color
+    }
+
+    /// Change the fill color without tessellating the corners again.
+    pub fn set_color(&mut self, color: graphics::Color) {
+        self.color = color;
+    }
 }
 
 impl graphics::Drawable for RoundedRectangle {
@@ -148,8 +227,10 @@ impl graphics::Drawable for RoundedRectangle {
     ) -> Option<graphics::Rect> {
         Some(self.bounds)
     }
+    /// Draws the rectangle in its color, multiplied with the color of `param` like any mesh.
     fn draw(&self, canvas: &mut graphics::Canvas, param: impl Into<graphics::DrawParam>) {
-        let param = param.into();
+        let param: graphics::DrawParam = param.into();
+        let param = param.color(multiply_colors(self.color, param.color));
         for mesh in &self.meshes {
             canvas.draw(mesh, param);
         }
@@ -0,0 +1,102 @@
+//! Modal listing the keyboard shortcuts, see `shortcuts::BINDINGS`.
+
+use ggez::{Context, GameResult, glam, graphics};
+
+use crate::{palette::PALETTE, shortcuts, typography};
+
+static DIALOG_WIDTH: f32 = 640.0;
+/// Space above the first shortcut, which fits the title.
+static HEADER_HEIGHT: f32 = 95.0;
+static LINE_HEIGHT: f32 = 36.0;
+static PADDING: f32 = 30.0;
+/// Width of the column with the keys.
+static KEY_COLUMN_WIDTH: f32 = 110.0;
+
+pub struct HelpOverlay {
+    /// The area which is dimmed behind the dialog.
+    backdrop_bounds: graphics::Rect,
+    bounds: graphics::Rect,
+}
+
+impl HelpOverlay {
+    /// Create an overlay centered over a scene of the specified size.
+    pub fn new(scene_size: glam::Vec2) -> Self {
+        let height = HEADER_HEIGHT + shortcuts::BINDINGS.len() as f32 * LINE_HEIGHT + PADDING;
+
+        Self {
+            backdrop_bounds: graphics::Rect::new(0.0, 0.0, scene_size.x, scene_size.y),
+            bounds: graphics::Rect {
+                x: scene_size.x / 2.0 - DIALOG_WIDTH / 2.0,
+                y: scene_size.y / 2.0 - height / 2.0,
+                w: DIALOG_WIDTH,
+                h: height,
+            },
+        }
+    }
+
+    pub fn draw(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        offset: glam::Vec2,
+    ) -> GameResult {
+        let mut backdrop_bounds = self.backdrop_bounds;
+        backdrop_bounds.translate(offset);
+        let backdrop = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            backdrop_bounds,
+            PALETTE.modal_backdrop,
+        )?;
+        canvas.draw(&backdrop, graphics::DrawParam::new());
+
+        let mut bounds = self.bounds;
+        bounds.translate(offset);
+        let background = graphics::Mesh::new_rounded_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            bounds,
+            15.0,
+            PALETTE.modal_background,
+        )?;
+        canvas.draw(&background, graphics::DrawParam::new());
+
+        let mut title = graphics::Text::new("Keyboard shortcuts");
+        title
+            .set_scale(graphics::PxScale::from(typography::SUBHEADING))
+            .set_layout(graphics::TextLayout {
+                h_align: graphics::TextAlign::Middle,
+                v_align: graphics::TextAlign::Begin,
+            });
+        canvas.draw(
+            &title,
+            graphics::DrawParam::new()
+                .color(PALETTE.text_neutral)
+                .dest(glam::vec2(bounds.center().x, bounds.top() + PADDING)),
+        );
+
+        for (line, &(shortcut, action)) in shortcuts::BINDINGS.iter().enumerate() {
+            let y = bounds.top() + HEADER_HEIGHT + line as f32 * LINE_HEIGHT;
+
+            let mut key_text = graphics::Text::new(shortcut.label());
+            key_text.set_scale(graphics::PxScale::from(typography::SMALL));
+            canvas.draw(
+                &key_text,
+                graphics::DrawParam::new()
+                    .color(PALETTE.text_neutral)
+                    .dest(glam::vec2(bounds.left() + PADDING, y)),
+            );
+
+            let mut description = graphics::Text::new(action.description());
+            description.set_scale(graphics::PxScale::from(typography::SMALL));
+            canvas.draw(
+                &description,
+                graphics::DrawParam::new()
+                    .color(PALETTE.text_subtle)
+                    .dest(glam::vec2(bounds.left() + PADDING + KEY_COLUMN_WIDTH, y)),
+            );
+        }
+
+        Ok(())
+    }
+}
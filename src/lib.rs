@@ -1,26 +1,83 @@
+//! A chess GUI which plays locally or against a remote over the chesstp protocol.
+//!
+//! Besides the GUI, the crate can be used as a library for tooling such as bots, loggers and test
+//! harnesses. The stable entry points are:
+//!
+//! - `chess_game::{Position, Color, PieceKind, Piece}` for describing squares and pieces.
+//! - `chess_game::BoardWrapper` for playing moves, which reports a `MoveOutcome` or `MoveError`.
+//! - `chess_game::{GameOutcome, EndReason, DrawReason}` for the result of a finished game, as
+//!   returned by `GameUi::result`.
+//! - `network::chesstp::{Message, MoveMessage, QuitMessage, ResignMessage, GamePhase, Board}` for
+//!   building, parsing and serializing protocol messages.
+//! - `network::ChesstpMessageStream` for exchanging messages over a `TcpStream`.
+//!
+//! Everything else, such as the UI types, may change without notice.
+
 use std::sync::Arc;
 
 use ggez::{
     GameError, GameResult, event,
     glam::{self, Vec2},
     graphics,
+    input::keyboard::{KeyCode, KeyInput},
     winit::dpi::PhysicalSize,
 };
 
-use crate::{assets::Assets, chess_game::GameUi};
+use crate::{
+    assets::Assets,
+    board_editor::BoardEditor,
+    chess_game::GameUi,
+    help_overlay::HelpOverlay,
+    palette::PALETTE,
+    quit_dialog::{QuitChoice, QuitDialog},
+    shortcuts::Action,
+};
 
-mod assets;
+pub mod assets;
+pub mod board_editor;
 pub mod chess_game;
 pub mod chess_graphics;
+pub mod help_overlay;
 pub mod network;
 pub mod palette;
+pub mod quit_dialog;
 mod rect;
+pub mod shortcuts;
+pub mod typography;
 pub mod ui;
 
+/// Position of the top left corner of the active scene.
+static SCENE_TOP_LEFT: Vec2 = Vec2::new(10.0, 10.0);
+
+/// The screen which is currently shown.
+enum Scene {
+    Editor(BoardEditor),
+    Game(GameUi),
+}
+
 pub struct MainState {
-    game: GameUi,
+    scene: Scene,
+    /// Shown on top of the scene when the user tries to close the window during a networked game.
+    quit_dialog: Option<QuitDialog>,
+    /// Set when a key press opens the quit dialog, so the character the key also types isn't
+    /// entered as the reason. Cleared by the next key press, in case no character was typed.
+    swallow_text_input: bool,
+    /// Lists the keyboard shortcuts on top of the scene while shown. Toggled with F1 or '?'.
+    help_overlay: Option<HelpOverlay>,
+    /// Set once the user has confirmed quitting, so the next quit event isn't intercepted.
+    quit_confirmed: bool,
+    /// The reason for leaving given in the quit dialog, sent to the remote when quitting.
+    quit_reason: String,
+    /// The minimum window size which was last set, if any.
+    applied_min_size: Option<Vec2>,
+    /// Whether the debug overlay showing the frame rate is drawn. Toggled with F3.
+    show_performance: bool,
+    /// The latest mouse position relative to the scene which hasn't been applied to hover states
+    /// yet. Motion events can fire many times per frame, so only the last one is applied in
+    /// `update`.
+    pending_mouse_position: Option<Vec2>,
     // connection: Arc<RefCell<network::GameConnection>>,
-    // assets: Arc<Assets>,
+    assets: Arc<Assets>,
 }
 
 impl MainState {
@@ -29,15 +86,47 @@ impl MainState {
         connection: network::GameConnection,
     ) -> GameResult<MainState> {
         let assets = Arc::new(Assets::new(ctx));
-        let state = MainState {
-            game: GameUi::new(ctx, glam::vec2(10.0, 10.0), &assets, connection)?,
-            // assets,
-        };
+        let game = GameUi::new(ctx, SCENE_TOP_LEFT, &assets, connection)?;
+
+        Ok(Self::with_scene(Scene::Game(game), assets))
+    }
+
+    /// Start a local game where the board turns to face whoever's turn it is after every move.
+    pub fn new_hot_seat(ctx: &mut ggez::Context) -> GameResult<MainState> {
+        let mut state = Self::new(ctx, network::GameConnection::Local)?;
+        if let Scene::Game(ref mut game) = state.scene {
+            game.set_auto_flip(true);
+        }
 
         Ok(state)
     }
 
-    fn mouse_left_button_event(
+    /// Start in the board editor, which starts a local game once the user has constructed a
+    /// position.
+    pub fn new_board_editor(ctx: &mut ggez::Context) -> GameResult<MainState> {
+        let assets = Arc::new(Assets::new(ctx));
+        let editor = BoardEditor::new(SCENE_TOP_LEFT, &assets);
+
+        Ok(Self::with_scene(Scene::Editor(editor), assets))
+    }
+
+    /// Start showing `scene`, with nothing shown on top of it yet.
+    fn with_scene(scene: Scene, assets: Arc<Assets>) -> MainState {
+        MainState {
+            scene,
+            quit_dialog: None,
+            swallow_text_input: false,
+            help_overlay: None,
+            quit_confirmed: false,
+            quit_reason: String::new(),
+            applied_min_size: None,
+            show_performance: false,
+            pending_mouse_position: None,
+            assets,
+        }
+    }
+
+    fn mouse_button_event(
         &mut self,
         _ctx: &mut ggez::Context,
         button: event::MouseButton,
@@ -45,49 +134,256 @@ impl MainState {
         x: f32,
         y: f32,
     ) {
-        if button != event::MouseButton::Left {
+        // Presses should see the same hover state as drawing.
+        self.apply_pending_mouse_position();
+
+        if let Some(ref mut dialog) = self.quit_dialog {
+            if button == event::MouseButton::Left {
+                dialog.update_with_press_state(Vec2::new(x, y), press_state);
+            }
+            return;
+        }
+        if self.help_overlay.is_some() {
             return;
         }
 
-        self.game
-            .update_with_press_state(Vec2::new(x, y), press_state);
+        match (&mut self.scene, button) {
+            (Scene::Editor(editor), event::MouseButton::Left) => {
+                editor.update_with_press_state(Vec2::new(x, y), press_state);
+            }
+            (Scene::Editor(editor), event::MouseButton::Right) => {
+                if press_state == ui::PressState::Pressed {
+                    editor.clear_square_at(Vec2::new(x, y));
+                }
+            }
+            (Scene::Game(game), event::MouseButton::Left) => {
+                game.update_with_press_state(Vec2::new(x, y), press_state);
+            }
+            _ => {}
+        }
+    }
+
+    /// Show the help overlay, or hide it if it's already shown.
+    fn toggle_help(&mut self) {
+        self.help_overlay = match self.help_overlay {
+            Some(_) => None,
+            None => Some(HelpOverlay::new(self.size())),
+        };
+    }
+
+    /// Carry out the action of a pressed shortcut, see `shortcuts::BINDINGS`.
+    fn perform(&mut self, ctx: &mut ggez::Context, action: Action) {
+        match action {
+            Action::FlipBoard => {
+                if let Scene::Game(ref mut game) = self.scene {
+                    game.flip();
+                }
+            }
+            Action::ToggleCompact => {
+                if let Scene::Game(ref mut game) = self.scene {
+                    game.set_compact(!game.compact());
+                }
+            }
+            Action::ToggleBlindfold => {
+                if let Scene::Game(ref mut game) = self.scene {
+                    game.set_blindfold(!game.blindfold());
+                }
+            }
+            Action::RevealPieces => {
+                if let Scene::Game(ref mut game) = self.scene {
+                    game.set_revealed(true);
+                }
+            }
+            Action::OfferOrAcceptDraw => {
+                if let Scene::Game(ref mut game) = self.scene {
+                    game.offer_or_accept_draw();
+                }
+            }
+            // The key resigns networked games by leaving them, which the quit dialog confirms. The
+            // sidebar button resigns without leaving.
+            Action::Resign => {
+                let size = self.size();
+                if let Scene::Game(ref mut game) = self.scene {
+                    if game.requires_quit_confirmation() {
+                        self.quit_dialog = Some(QuitDialog::new(size));
+                        self.swallow_text_input = true;
+                    } else {
+                        game.request_resignation();
+                    }
+                }
+            }
+            // Keep the default behavior of quitting on escape, unless there's a staged move to
+            // cancel.
+            Action::CancelOrQuit => {
+                let cancelled = match self.scene {
+                    Scene::Game(ref mut game) => game.cancel_staged_move(),
+                    Scene::Editor(_) => false,
+                };
+                if !cancelled {
+                    ctx.request_quit();
+                }
+            }
+            Action::UndoMove => {
+                if let Scene::Game(ref mut game) = self.scene {
+                    game.undo_move();
+                }
+            }
+            Action::ToggleHelp => self.toggle_help(),
+            Action::TogglePerformance => self.show_performance = !self.show_performance,
+        }
+    }
+
+    /// Update hover states with the latest mouse position, if it moved since the last call.
+    fn apply_pending_mouse_position(&mut self) {
+        let Some(position) = self.pending_mouse_position.take() else {
+            return;
+        };
+
+        if let Some(ref mut dialog) = self.quit_dialog {
+            dialog.update_with_mouse_position(position);
+            return;
+        }
+
+        match self.scene {
+            Scene::Editor(ref mut editor) => editor.update_with_mouse_position(position),
+            Scene::Game(ref mut game) => game.update_with_mouse_position(position),
+        }
+    }
+
+    /// The window size required to fit the active scene.
+    pub fn size(&self) -> glam::Vec2 {
+        match self.scene {
+            Scene::Editor(ref editor) => editor.size(),
+            Scene::Game(ref game) => game.size(),
+        }
+    }
+
+    /// Draw the frame rate and frame time in the top left corner of the window, for debugging
+    /// rendering performance. During a game, the draw calls spent on pieces are listed too, along
+    /// with how many they would be without batching.
+    fn draw_performance_overlay(&self, ctx: &ggez::Context, canvas: &mut graphics::Canvas) {
+        let mut content = format!(
+            "{:.0} FPS, {:.1} ms",
+            ctx.time.fps(),
+            ctx.time.delta().as_secs_f64() * 1000.0
+        );
+        if let Scene::Game(ref game) = self.scene {
+            let (batched, unbatched) = game.piece_draw_calls();
+            content += &format!("\n{} piece draw calls, {} unbatched", batched, unbatched);
+        }
+        let mut text = graphics::Text::new(content);
+        text.set_scale(graphics::PxScale::from(typography::SMALL));
+        canvas.draw(
+            &text,
+            graphics::DrawParam::new()
+                .color(PALETTE.text_subtle)
+                .dest(glam::vec2(5.0, 5.0)),
+        );
     }
 
-    /// Calculates the appropriate offset to keep the `GameUi` struct centered in within the window.
+    /// Calculates the appropriate offset to keep the active scene centered in within the window.
     fn center_offset(&self, ctx: &ggez::Context) -> glam::Vec2 {
         // let window_size = ctx.gfx.window().inner_size().to_logical::<f32>(ctx.gfx.window().scale_factor());
         let window_size = ctx.gfx.window().inner_size().cast::<f32>();
 
-        glam::vec2(window_size.width / 2.0 - GameUi::size().x / 2.0, 0.0)
+        glam::vec2(window_size.width / 2.0 - self.size().x / 2.0, 0.0)
     }
 }
 
 impl event::EventHandler<GameError> for MainState {
     fn update(&mut self, ctx: &mut ggez::Context) -> GameResult<()> {
-        let min_size = GameUi::size();
-        ctx.gfx
-            .window()
-            .set_min_inner_size(Some(PhysicalSize::new(min_size.x, min_size.y)));
+        // The size only changes with the scene, so avoid calling into the window every frame.
+        let min_size = self.size();
+        if self.applied_min_size != Some(min_size) {
+            ctx.gfx
+                .window()
+                .set_min_inner_size(Some(PhysicalSize::new(min_size.x, min_size.y)));
+            self.applied_min_size = Some(min_size);
+        }
+
+        match self.quit_dialog.as_ref().and_then(|dialog| dialog.choice()) {
+            Some(QuitChoice::ResignAndQuit) => {
+                self.quit_reason = self
+                    .quit_dialog
+                    .take()
+                    .map(|dialog| dialog.reason().to_owned())
+                    .unwrap_or_default();
+                self.quit_confirmed = true;
+                ctx.request_quit();
+            }
+            Some(QuitChoice::Cancel) => self.quit_dialog = None,
+            None => {}
+        }
 
-        self.game.update();
+        self.apply_pending_mouse_position();
+
+        match self.scene {
+            Scene::Editor(ref mut editor) => {
+                if let Some(board) = editor.take_finished_board() {
+                    self.scene = Scene::Game(GameUi::with_board(
+                        ctx,
+                        SCENE_TOP_LEFT,
+                        &self.assets,
+                        board,
+                        network::GameConnection::Local,
+                    )?);
+                }
+            }
+            Scene::Game(ref mut game) => game.update(),
+        }
 
         Ok(())
     }
 
     fn draw(&mut self, ctx: &mut ggez::Context) -> GameResult<()> {
-        let mut canvas = graphics::Canvas::from_frame(ctx, graphics::Color::from_rgb_u32(0x2E2B28));
+        let mut canvas = graphics::Canvas::from_frame(ctx, PALETTE.window_background);
+        if let Some(bottom_color) = PALETTE.window_background_gradient {
+            let (width, height) = ctx.gfx.drawable_size();
+            let gradient = chess_graphics::vertical_gradient(
+                ctx,
+                graphics::Rect::new(0.0, 0.0, width, height),
+                PALETTE.window_background,
+                bottom_color,
+            );
+            canvas.draw(&gradient, graphics::DrawParam::new());
+        }
 
-        self.game.draw(ctx, &mut canvas, self.center_offset(ctx))?;
+        let offset = self.center_offset(ctx);
+        match self.scene {
+            Scene::Editor(ref editor) => editor.draw(ctx, &mut canvas, offset)?,
+            Scene::Game(ref mut game) => game.draw(ctx, &mut canvas, offset)?,
+        }
+        if let Some(ref dialog) = self.quit_dialog {
+            dialog.draw(ctx, &mut canvas, offset)?;
+        }
+        if let Some(ref help) = self.help_overlay {
+            help.draw(ctx, &mut canvas, offset)?;
+        }
+        if self.show_performance {
+            self.draw_performance_overlay(ctx, &mut canvas);
+        }
 
         canvas.finish(ctx)
     }
 
     fn quit_event(&mut self, _ctx: &mut ggez::Context) -> Result<bool, GameError> {
+        if let Scene::Game(ref game) = self.scene {
+            if !self.quit_confirmed && game.requires_quit_confirmation() {
+                // Returning true cancels the close, the dialog quits for real once confirmed.
+                if self.quit_dialog.is_none() {
+                    self.quit_dialog = Some(QuitDialog::new(self.size()));
+                }
+                return Ok(true);
+            }
+        }
+
         println!("Quiting...");
 
-        self.game.quit_event().unwrap_or_else(|error| {
-            println!("Sending quit failed: {}", error);
-        });
+        if let Scene::Game(ref mut game) = self.scene {
+            game.quit_event(&self.quit_reason).unwrap_or_else(|error| {
+                println!("Sending quit failed: {}", error);
+            });
+        }
 
         Ok(false)
     }
@@ -99,7 +395,7 @@ impl event::EventHandler<GameError> for MainState {
         x: f32,
         y: f32,
     ) -> GameResult {
-        self.mouse_left_button_event(
+        self.mouse_button_event(
             ctx,
             button,
             ui::PressState::Pressed,
@@ -115,7 +411,7 @@ impl event::EventHandler<GameError> for MainState {
         x: f32,
         y: f32,
     ) -> GameResult {
-        self.mouse_left_button_event(
+        self.mouse_button_event(
             ctx,
             button,
             ui::PressState::Released,
@@ -124,6 +420,66 @@ impl event::EventHandler<GameError> for MainState {
         );
         Ok(())
     }
+    fn text_input_event(&mut self, _ctx: &mut ggez::Context, character: char) -> GameResult {
+        if self.swallow_text_input {
+            self.swallow_text_input = false;
+        } else if let Some(ref mut dialog) = self.quit_dialog {
+            dialog.type_char(character);
+        } else if character == '?' {
+            self.toggle_help();
+        }
+        Ok(())
+    }
+
+    fn key_down_event(
+        &mut self,
+        ctx: &mut ggez::Context,
+        input: KeyInput,
+        _repeated: bool,
+    ) -> GameResult {
+        self.swallow_text_input = false;
+        let Some(key) = input.keycode else {
+            return Ok(());
+        };
+        let action = shortcuts::action_for(key, input.mods);
+
+        if let Some(ref mut dialog) = self.quit_dialog {
+            // Letters go to the reason field while the dialog is open.
+            match (key, action) {
+                (KeyCode::Back, _) => dialog.backspace(),
+                (KeyCode::Escape, _) => self.quit_dialog = None,
+                (_, Some(Action::TogglePerformance)) => {
+                    self.show_performance = !self.show_performance
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        // The help overlay is modal, and is dismissed with the key which opened it or escape.
+        if self.help_overlay.is_some() {
+            match action {
+                Some(Action::ToggleHelp | Action::CancelOrQuit) => self.help_overlay = None,
+                Some(Action::TogglePerformance) => self.show_performance = !self.show_performance,
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        if let Some(action) = action {
+            self.perform(ctx, action);
+        }
+        Ok(())
+    }
+
+    fn key_up_event(&mut self, _ctx: &mut ggez::Context, input: KeyInput) -> GameResult {
+        let action = input.keycode.and_then(shortcuts::held_action_for);
+        if let (Scene::Game(game), Some(Action::RevealPieces)) = (&mut self.scene, action) {
+            game.set_revealed(false);
+        }
+        Ok(())
+    }
+
     fn mouse_motion_event(
         &mut self,
         ctx: &mut ggez::Context,
@@ -132,8 +488,7 @@ impl event::EventHandler<GameError> for MainState {
         _dx: f32,
         _dy: f32,
     ) -> Result<(), GameError> {
-        self.game
-            .update_with_mouse_position(Vec2::new(x, y) - self.center_offset(ctx));
+        self.pending_mouse_position = Some(Vec2::new(x, y) - self.center_offset(ctx));
         Ok(())
     }
 }
@@ -1,6 +1,7 @@
 use std::{
     io,
-    net::{Ipv4Addr, SocketAddrV4, TcpListener, TcpStream},
+    net::{SocketAddr, TcpStream},
+    time::{Duration, Instant},
 };
 
 use anyhow::anyhow;
@@ -8,78 +9,203 @@ use ggez::{conf, event};
 
 use rsoderh_gui::{
     MainState,
-    chess_game::GameUi,
-    network::{ChesstpMessageStream, ConnectionType, GameConnection, setup},
+    chess_game::BoardWrapper,
+    network::{ChesstpMessageStream, ConnectionType, GameConnection, discovery, setup},
 };
 
+/// How long the remote may stall in the middle of sending a message before it's considered
+/// unresponsive.
+static FRAME_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a single attempt to connect to the server may take.
+static CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// How many times to try connecting to the server before giving up.
+static CONNECT_ATTEMPTS: u32 = 5;
+/// How long to wait before retrying to connect to the server, doubling after every retry.
+static CONNECT_BACKOFF: Duration = Duration::from_millis(500);
+/// How long an accepted connection is watched for the peer hanging up right away, like port
+/// scanners do.
+static ACCEPT_PROBE_DURATION: Duration = Duration::from_millis(500);
+/// How long to wait between checks for new connections while serving, so that waiting doesn't
+/// keep a core busy.
+static ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// Known perft results for the starting position, indexed by depth.
+static STARTING_POSITION_PERFT: [u64; 7] = [1, 20, 400, 8_902, 197_281, 4_865_609, 119_060_324];
+
+/// What watching a freshly accepted connection has found so far, see `probe_peer`.
+enum Probe {
+    /// The peer neither sent anything nor hung up yet.
+    Pending,
+    HungUp,
+    /// The peer sent something, or kept the connection open for `ACCEPT_PROBE_DURATION`.
+    Alive,
+}
+
+/// Check whether the peer of a freshly accepted, non-blocking `stream` has hung up, without
+/// blocking or consuming anything it sent. A peer which hasn't within `ACCEPT_PROBE_DURATION` of
+/// `accepted_at` counts as alive.
+fn probe_peer(stream: &TcpStream, accepted_at: Instant) -> io::Result<Probe> {
+    match stream.peek(&mut [0u8; 1]) {
+        Ok(0) => Ok(Probe::HungUp),
+        Ok(_) => Ok(Probe::Alive),
+        Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => {
+            if accepted_at.elapsed() >= ACCEPT_PROBE_DURATION {
+                Ok(Probe::Alive)
+            } else {
+                Ok(Probe::Pending)
+            }
+        }
+        Err(error) if error.kind() == io::ErrorKind::ConnectionReset => Ok(Probe::HungUp),
+        Err(error) => Err(error),
+    }
+}
+
+/// Count the positions reachable from the starting position for every depth up to `max_depth`,
+/// comparing against the known numbers. Used through the hidden `--perft <depth>` flag.
+fn run_perft(max_depth: u32) -> Result<(), anyhow::Error> {
+    let board = BoardWrapper::new(chess::game::game_state::new());
+
+    let mut mismatches = 0;
+    for depth in 1..=max_depth {
+        let started_at = Instant::now();
+        let count = board.perft(depth);
+        let elapsed = started_at.elapsed();
+
+        match STARTING_POSITION_PERFT.get(depth as usize) {
+            Some(&expected) if expected == count => {
+                println!("perft({}) = {} ({:?})", depth, count, elapsed);
+            }
+            Some(&expected) => {
+                println!(
+                    "perft({}) = {} ({:?}), MISMATCH: expected {}, off by {}",
+                    depth,
+                    count,
+                    elapsed,
+                    expected,
+                    count as i64 - expected as i64
+                );
+                mismatches += 1;
+            }
+            None => println!("perft({}) = {} ({:?}), no reference", depth, count, elapsed),
+        }
+    }
+
+    if mismatches > 0 {
+        return Err(anyhow!("{} perft depth(s) didn't match", mismatches));
+    }
+    Ok(())
+}
+
 pub fn main() -> Result<(), anyhow::Error> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let [flag, depth] = &args[..] {
+        if flag == "--perft" {
+            let depth = depth
+                .parse()
+                .map_err(|_| anyhow!("--perft expects a depth, got \"{}\"", depth))?;
+            return run_perft(depth);
+        }
+    }
+
     let config = setup::prompt_network_config().unwrap();
     println!("Got config {:?}", config);
 
     let connection = match config {
-        setup::NetworkConfig::Local => GameConnection::Local,
+        setup::NetworkConfig::Local
+        | setup::NetworkConfig::HotSeat
+        | setup::NetworkConfig::Editor => GameConnection::Local,
+        setup::NetworkConfig::Analysis => GameConnection::Analysis,
         setup::NetworkConfig::Client(socket_addr) => {
-            println!("Connecting to {}...", socket_addr);
-            let stream = match TcpStream::connect(socket_addr) {
-                Ok(stream) => stream,
-                Err(error) => {
-                    return Err(anyhow!("Could not connect to {}: {}", socket_addr, error));
-                }
-            };
-            println!("Connected, starting game");
-            GameConnection::Remote(
-                ConnectionType::Client,
+            let stream = setup::connect_with_retries(
                 socket_addr,
-                ChesstpMessageStream::new(stream)?,
+                CONNECT_TIMEOUT,
+                CONNECT_ATTEMPTS,
+                CONNECT_BACKOFF,
             )
+            .map_err(|error| {
+                anyhow!(
+                    "Could not connect to {} after {} attempts: {}",
+                    socket_addr,
+                    CONNECT_ATTEMPTS,
+                    error
+                )
+            })?;
+            println!("Connected, starting game");
+            let mut stream = ChesstpMessageStream::new(stream)?;
+            stream.set_frame_timeout(Some(FRAME_TIMEOUT));
+            GameConnection::Remote(ConnectionType::Client, socket_addr, stream)
         }
-        setup::NetworkConfig::Server(port) => {
-            let addr_v4 = SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), port);
-            let listener_v4 = TcpListener::bind(addr_v4)?;
-            listener_v4.set_nonblocking(true)?;
-            println!("Bound ipv4");
-
-            // let addr_v6 = SocketAddrV6::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), port, 0, 0);
-            // let listener_v6 = TcpListener::bind(addr_v6)?;
-            // listener_v6.set_nonblocking(true)?;
-            // println!("Bound ipv6");
-
-            println!("Listening on {}", addr_v4);
-            // println!("Listening on {}", addr_v6);
+        setup::NetworkConfig::Server(port, interface) => {
+            let listeners = setup::bind_server(port, interface)?;
+            for listener in &listeners {
+                println!("Listening on {}", listener.local_addr()?);
+            }
             println!("Waiting for connection...");
 
-            let (stream, socket_addr) = loop {
-                match listener_v4.accept() {
-                    Ok((stream, socket_addr)) => break (stream, socket_addr),
-                    Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => {}
-                    Err(error) => return Err(anyhow!(error)),
+            // Nobody on the local network could connect to a loopback-only server.
+            let mut announcer = if interface.is_some_and(|interface| interface.is_loopback()) {
+                None
+            } else {
+                match discovery::Announcer::new(&discovery::default_server_name(), port) {
+                    Ok(announcer) => Some(announcer),
+                    Err(error) => {
+                        println!("Could not announce game on the local network: {}", error);
+                        None
+                    }
                 }
-                // match listener_v6.accept() {
-                //     Ok(pair) => break pair,
-                //     Err(ref error)  if error.kind() == io::ErrorKind::WouldBlock => {},
-                //     Err(error) => return Err(anyhow!(error)),
-                // }
             };
 
-            stream.set_nonblocking(true)?;
+            // Accepted connections which are still being watched for hanging up, in the order
+            // they were accepted. Watching them without blocking keeps the announcer going.
+            let mut probing: Vec<(TcpStream, SocketAddr, Instant)> = Vec::new();
+            let (stream, socket_addr) = 'accept: loop {
+                if let Some(Err(error)) = announcer.as_mut().map(|announcer| announcer.tick()) {
+                    println!("Could not announce game on the local network: {}", error);
+                    announcer = None;
+                }
+
+                if let Some((stream, socket_addr)) = setup::accept_any(&listeners)? {
+                    stream.set_nonblocking(true)?;
+                    probing.push((stream, socket_addr, Instant::now()));
+                }
+
+                let mut index = 0;
+                while index < probing.len() {
+                    let (ref stream, socket_addr, accepted_at) = probing[index];
+                    match probe_peer(stream, accepted_at) {
+                        Ok(Probe::Pending) => index += 1,
+                        Ok(Probe::Alive) => {
+                            let (stream, socket_addr, _) = probing.remove(index);
+                            break 'accept (stream, socket_addr);
+                        }
+                        Ok(Probe::HungUp) => {
+                            println!("{} hung up right away, still waiting...", socket_addr);
+                            probing.remove(index);
+                        }
+                        Err(error) => {
+                            println!(
+                                "Dropping connection from {}: {}, still waiting...",
+                                socket_addr, error
+                            );
+                            probing.remove(index);
+                        }
+                    }
+                }
+
+                std::thread::sleep(ACCEPT_POLL_INTERVAL);
+            };
 
             println!("Connected to {}, starting game", socket_addr);
 
-            GameConnection::Remote(
-                ConnectionType::Server,
-                socket_addr,
-                ChesstpMessageStream::new(stream)?,
-            )
+            let mut stream = ChesstpMessageStream::new(stream)?;
+            stream.set_frame_timeout(Some(FRAME_TIMEOUT));
+            GameConnection::Remote(ConnectionType::Server, socket_addr, stream)
         }
     };
 
     // TcpListener::bind(addr);
 
-    let min_size = GameUi::size();
     let cb = ggez::ContextBuilder::new("rsoderh_chess_gui", "ggez")
         .window_mode(conf::WindowMode {
-            width: min_size.x,
-            height: min_size.y,
             resizable: true,
             // resize_on_scale_factor_change: true,
             ..Default::default()
@@ -89,6 +215,15 @@ pub fn main() -> Result<(), anyhow::Error> {
             ..conf::WindowSetup::default()
         });
     let (mut ctx, event_loop) = cb.build()?;
-    let state = MainState::new(&mut ctx, connection)?;
+    let state = match config {
+        setup::NetworkConfig::Editor => MainState::new_board_editor(&mut ctx)?,
+        setup::NetworkConfig::HotSeat => MainState::new_hot_seat(&mut ctx)?,
+        _ => MainState::new(&mut ctx, connection)?,
+    };
+
+    // The required size depends on measured text, so it's only known once the UI exists.
+    let size = state.size();
+    ctx.gfx.set_drawable_size(size.x, size.y)?;
+
     event::run(ctx, event_loop, state)
 }
@@ -11,6 +11,24 @@ use crate::chess_game::{Color, Piece, PieceKind, Position};
 
 mod tests;
 
+/// The standard sync marker which every chesstp message identifier starts with, e.g. "ChessMOVE".
+/// Variants of the protocol may use another one, see `Message::parse_from_with_prefix`.
+pub static DEFAULT_PREFIX: &str = "Chess";
+
+/// Most bytes a serialized `MoveMessage` takes: the move with a promotion, the game phase and a
+/// board with a piece on every square, separated by ':'.
+static MAX_MOVE_MESSAGE_LEN: usize = 5 + 1 + 3 + 1 + 71;
+
+/// Longest prefix which leaves room in a frame for every move message, see
+/// `Message::serialize_with_prefix`.
+pub static MAX_PREFIX_LEN: usize = 128 - ("MOVE".len() + MAX_MOVE_MESSAGE_LEN + 2);
+
+/// Most bytes the message of a `QuitMessage` can have in a frame whose identifiers start with
+/// `prefix`. Longer messages are cut off when serialized.
+pub fn max_quit_message_len(prefix: &str) -> usize {
+    128usize.saturating_sub(prefix.len() + "QUIT".len() + 2)
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum BoardParseError {
     /// Invalid character (i.e. not `[PNBRQKpnbrqk0-9]`) found which was expected to represent a
@@ -20,6 +38,80 @@ pub enum BoardParseError {
     InvalidColumnCount(usize),
 }
 
+impl std::fmt::Display for BoardParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidTileCharacter(char_) => write!(f, "invalid tile {:?}", char_),
+            Self::InvalidRowCount(count) => write!(f, "{} rows instead of 8", count),
+            Self::InvalidColumnCount(count) => {
+                write!(f, "a row with {} columns instead of 8", count)
+            }
+        }
+    }
+}
+
+/// Why a tile of a `Board` couldn't be read or written.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum TileError {
+    /// The `board` or `player` vectors are too short to contain the tile.
+    OutOfBounds,
+    /// The tile contains a piece name which isn't known.
+    InvalidPiece(String),
+    /// The tile contains a player other than 'w', 'b' and ' '.
+    InvalidPlayer(char),
+}
+
+impl std::fmt::Display for TileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OutOfBounds => f.write_str("tile is outside the board"),
+            Self::InvalidPiece(piece) => write!(f, "unknown piece {:?}", piece),
+            Self::InvalidPlayer(player) => write!(f, "unknown player {:?}", player),
+        }
+    }
+}
+
+/// Why a board doesn't describe a position which can occur in a game.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BoardValidationError {
+    /// The tile is missing or contains something other than a piece, see `TileError`.
+    MalformedTile(Position),
+    /// The color doesn't have exactly one king. Contains the number of kings found.
+    KingCount(Color, usize),
+    /// The color has more than 8 pawns. Contains the number of pawns found.
+    TooManyPawns(Color, usize),
+    /// The color has more than 16 pieces. Contains the number of pieces found.
+    TooManyPieces(Color, usize),
+    /// The color has more pieces beyond the starting set than it could have promoted pawns into.
+    TooManyPromotedPieces(Color),
+}
+
+impl std::fmt::Display for BoardValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MalformedTile(position) => write!(f, "tile {:?} is malformed", position),
+            Self::KingCount(color, count) => {
+                write!(f, "{:?} needs exactly one king (has {})", color, count)
+            }
+            Self::TooManyPawns(color, count) => {
+                write!(f, "{:?} has too many pawns ({} of at most 8)", color, count)
+            }
+            Self::TooManyPieces(color, count) => {
+                write!(
+                    f,
+                    "{:?} has too many pieces ({} of at most 16)",
+                    color, count
+                )
+            }
+            Self::TooManyPromotedPieces(color) => write!(
+                f,
+                "{:?} has more extra pieces than it could have promoted",
+                color
+            ),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum ParseError {
     /// The parsed buffer didn't contain valid UTF-8.
@@ -36,6 +128,22 @@ pub enum ParseError {
     /// part (i.e. all characters between the two surrounding ':').
     InvalidGamePhase(String),
     InvalidBoard(String, BoardParseError),
+    /// The message's color part didn't match the format. Contains the entire part.
+    InvalidColor(String),
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Utf8Error(_) => f.write_str("message isn't valid UTF-8"),
+            Self::InvalidMessageId(id) => write!(f, "unknown message type {:?}", id),
+            Self::TooFewParts(count) => write!(f, "message has only {} parts", count),
+            Self::InvalidMove(move_) => write!(f, "malformed move {:?}", move_),
+            Self::InvalidGamePhase(phase) => write!(f, "malformed game state {:?}", phase),
+            Self::InvalidBoard(_, error) => write!(f, "malformed board: {}", error),
+            Self::InvalidColor(color) => write!(f, "malformed color {:?}", color),
+        }
+    }
 }
 
 impl From<Utf8Error> for ParseError {
@@ -103,36 +211,22 @@ impl<'a> Iterator for FenBoardRowPieces<'a> {
                 Some(Ok(None))
             }
 
-            piece_char
-            @ ('p' | 'n' | 'b' | 'r' | 'q' | 'k' | 'P' | 'N' | 'B' | 'R' | 'Q' | 'K') => {
+            piece_char => {
+                let mut row_chars = self.row.chars();
+                row_chars.next();
+                self.row = row_chars.as_str();
+
+                let Some(kind) = PieceKind::from_fen_char(piece_char) else {
+                    return Some(Err(BoardParseError::InvalidTileCharacter(piece_char)));
+                };
                 let color = if piece_char.is_ascii_uppercase() {
                     Color::White
                 } else {
                     Color::Black
                 };
-                let kind = match piece_char.to_ascii_lowercase() {
-                    'p' => PieceKind::Pawn,
-                    'n' => PieceKind::Knight,
-                    'b' => PieceKind::Bishop,
-                    'r' => PieceKind::Rook,
-                    'q' => PieceKind::Queen,
-                    'k' => PieceKind::King,
-                    _ => unreachable!(),
-                };
-
-                let mut row_chars = self.row.chars();
-                row_chars.next();
-                self.row = row_chars.as_str();
 
                 Some(Ok(Some(Piece { kind, color })))
             }
-
-            invalid_char => {
-                let mut row_chars = self.row.chars();
-                row_chars.next();
-                self.row = row_chars.as_str();
-                Some(Err(BoardParseError::InvalidTileCharacter(invalid_char)))
-            }
         }
     }
 }
@@ -152,45 +246,119 @@ impl Board {
         Self { board, player }
     }
 
-    pub fn tile(&self, tile: Position) -> Option<Piece> {
-        let kind =
-            match self.board[(tile.row() + 1) as usize][(tile.column() + 1) as usize].as_str() {
-                "pawn" => PieceKind::Pawn,
-                "knight" => PieceKind::Knight,
-                "bishop" => PieceKind::Bishop,
-                "rook" => PieceKind::Rook,
-                "queen" => PieceKind::Queen,
-                "king" => PieceKind::King,
-                "empty" => return None,
-                _ => unreachable!(),
-            };
-
-        let color = match self.player[(tile.row() + 1) as usize][(tile.column() + 1) as usize] {
-            'w' => Color::White,
-            'b' => Color::Black,
-            ' ' => return None,
-            _ => unreachable!(),
+    /// Returns the piece on `tile`, or an error if the board is malformed there, e.g. since it was
+    /// constructed by hand or received from a buggy remote.
+    pub fn tile(&self, tile: Position) -> Result<Option<Piece>, TileError> {
+        let (row, column) = ((tile.row() + 1) as usize, (tile.column() + 1) as usize);
+        let piece_str = self
+            .board
+            .get(row)
+            .and_then(|row| row.get(column))
+            .ok_or(TileError::OutOfBounds)?;
+        let player = *self
+            .player
+            .get(row)
+            .and_then(|row| row.get(column))
+            .ok_or(TileError::OutOfBounds)?;
+
+        let kind = match piece_str.as_str() {
+            "empty" => return Ok(None),
+            name => PieceKind::from_library_name(name)
+                .ok_or(TileError::InvalidPiece(name.to_owned()))?,
+        };
+
+        let color = match player {
+            ' ' => return Ok(None),
+            _ => Color::from_fen_char(player).ok_or(TileError::InvalidPlayer(player))?,
+        };
+
+        Ok(Some(Piece { kind, color }))
+    }
+
+    /// Put `piece` on `tile`. Fails only if the board vectors are too short to contain the tile.
+    pub fn set_tile(&mut self, tile: Position, piece: Option<Piece>) -> Result<(), TileError> {
+        let (row, column) = ((tile.row() + 1) as usize, (tile.column() + 1) as usize);
+        let piece_str = self
+            .board
+            .get_mut(row)
+            .and_then(|row| row.get_mut(column))
+            .ok_or(TileError::OutOfBounds)?;
+        let player = self
+            .player
+            .get_mut(row)
+            .and_then(|row| row.get_mut(column))
+            .ok_or(TileError::OutOfBounds)?;
+
+        *piece_str = match piece.map(|piece| piece.kind) {
+            Some(PieceKind::Pawn) => "pawn".to_owned(),
+            Some(PieceKind::Knight) => "knight".to_owned(),
+            Some(PieceKind::Bishop) => "bishop".to_owned(),
+            Some(PieceKind::Rook) => "rook".to_owned(),
+            Some(PieceKind::Queen) => "queen".to_owned(),
+            Some(PieceKind::King) => "king".to_owned(),
+            None => "empty".to_owned(),
         };
+        *player = piece.map_or(' ', |piece| piece.color.to_fen_char());
+
+        Ok(())
+    }
+
+    /// Count how many tiles contain the specified piece.
+    pub fn count(&self, piece: Piece) -> usize {
+        (0..8)
+            .flat_map(|row| (0..8).map(move |column| (column, row)))
+            .filter_map(Position::from_pair)
+            .filter(|&position| self.tile(position) == Ok(Some(piece)))
+            .count()
+    }
+
+    /// Check that the piece counts of both colors are possible in a game, i.e. exactly one king,
+    /// at most 8 pawns, and no more extra pieces than promoted pawns. Doesn't check that the
+    /// position is reachable.
+    pub fn validate(&self) -> Result<(), BoardValidationError> {
+        if let Some(position) = Position::all().find(|&position| self.tile(position).is_err()) {
+            return Err(BoardValidationError::MalformedTile(position));
+        }
+
+        for color in [Color::White, Color::Black] {
+            let count = |kind| self.count(Piece { kind, color });
+
+            let kings = count(PieceKind::King);
+            if kings != 1 {
+                return Err(BoardValidationError::KingCount(color, kings));
+            }
+
+            let pawns = count(PieceKind::Pawn);
+            if pawns > 8 {
+                return Err(BoardValidationError::TooManyPawns(color, pawns));
+            }
 
-        return Some(Piece { kind, color });
-    }
-    pub fn set_tile(&mut self, tile: Position, piece: Option<Piece>) {
-        self.board[(tile.row() + 1) as usize][(tile.column() + 1) as usize] =
-            match piece.map(|piece| piece.kind) {
-                Some(PieceKind::Pawn) => "pawn".to_owned(),
-                Some(PieceKind::Knight) => "knight".to_owned(),
-                Some(PieceKind::Bishop) => "bishop".to_owned(),
-                Some(PieceKind::Rook) => "rook".to_owned(),
-                Some(PieceKind::Queen) => "queen".to_owned(),
-                Some(PieceKind::King) => "king".to_owned(),
-                None => "empty".to_owned(),
-            };
-        self.player[(tile.row() + 1) as usize][(tile.column() + 1) as usize] =
-            match piece.map(|piece| piece.color) {
-                Some(Color::White) => 'w',
-                Some(Color::Black) => 'b',
-                None => ' ',
-            };
+            let starting_counts = [
+                (PieceKind::Queen, 1),
+                (PieceKind::Rook, 2),
+                (PieceKind::Bishop, 2),
+                (PieceKind::Knight, 2),
+            ];
+            let pieces = kings
+                + pawns
+                + starting_counts
+                    .iter()
+                    .map(|&(kind, _)| count(kind))
+                    .sum::<usize>();
+            if pieces > 16 {
+                return Err(BoardValidationError::TooManyPieces(color, pieces));
+            }
+
+            let promoted: usize = starting_counts
+                .iter()
+                .map(|&(kind, starting)| count(kind).saturating_sub(starting))
+                .sum();
+            if promoted > 8 - pawns {
+                return Err(BoardValidationError::TooManyPromotedPieces(color));
+            }
+        }
+
+        Ok(())
     }
 
     pub fn update_game(self, game: &mut chess::game::game_state) {
@@ -198,6 +366,8 @@ impl Board {
         game.player = self.player;
     }
 
+    /// Serialize to the "piece placement data" part of FEN. Malformed tiles are written as '?', so
+    /// that parsing the result fails.
     pub fn serialize(&self) -> String {
         (0..8)
             .rev()
@@ -210,16 +380,17 @@ impl Board {
                         (1, self.tile(tile))
                     })
                     .coalesce(|previous, current| {
-                        if previous.1.is_none() && current.1.is_none() {
-                            Ok((previous.0 + current.0, None))
+                        if previous.1 == Ok(None) && current.1 == Ok(None) {
+                            Ok((previous.0 + current.0, Ok(None)))
                         } else {
                             Err((previous, current))
                         }
                     })
                     .map(|(count, tile)| match tile {
-                        None => char::from_digit(count, 10)
+                        Err(_) => '?',
+                        Ok(None) => char::from_digit(count, 10)
                             .expect("there can't be more than 8 consecutive None values"),
-                        Some(Piece { kind, color }) => {
+                        Ok(Some(Piece { kind, color })) => {
                             let piece_char = match kind {
                                 PieceKind::Pawn => 'p',
                                 PieceKind::Knight => 'n',
@@ -239,6 +410,47 @@ impl Board {
             })
             .join("/")
     }
+
+    /// Render as lines of Unicode chess glyphs from rank 8 down, with the rank numbers on the left
+    /// and the files below, e.g. for logging. Empty tiles are drawn as '·' and malformed ones as
+    /// '?'. Tiles are separated by spaces, since some terminals draw the glyphs double width.
+    pub fn to_unicode(&self) -> String {
+        let mut lines = (0..8)
+            .rev()
+            .map(|row_index| {
+                let tiles = (0..8)
+                    .map(|column_index| {
+                        let tile =
+                            Position::new(column_index, row_index).expect("Indices are in 0..8");
+
+                        match self.tile(tile) {
+                            Err(_) => '?',
+                            Ok(None) => '·',
+                            Ok(Some(Piece { kind, color })) => match (color, kind) {
+                                (Color::White, PieceKind::King) => '♔',
+                                (Color::White, PieceKind::Queen) => '♕',
+                                (Color::White, PieceKind::Rook) => '♖',
+                                (Color::White, PieceKind::Bishop) => '♗',
+                                (Color::White, PieceKind::Knight) => '♘',
+                                (Color::White, PieceKind::Pawn) => '♙',
+                                (Color::Black, PieceKind::King) => '♚',
+                                (Color::Black, PieceKind::Queen) => '♛',
+                                (Color::Black, PieceKind::Rook) => '♜',
+                                (Color::Black, PieceKind::Bishop) => '♝',
+                                (Color::Black, PieceKind::Knight) => '♞',
+                                (Color::Black, PieceKind::Pawn) => '♟',
+                            },
+                        }
+                    })
+                    .join(" ");
+
+                format!("{} {}", row_index + 1, tiles)
+            })
+            .collect::<Vec<_>>();
+        lines.push("  a b c d e f g h".to_owned());
+
+        lines.join("\n")
+    }
 }
 
 impl FromStr for Board {
@@ -279,7 +491,9 @@ impl FromStr for Board {
             let position = Position::new(column_index as u8, row_index as u8)
                 .expect("columns and rows have been checked to be in 0..8");
 
-            board.set_tile(position, piece?);
+            board
+                .set_tile(position, piece?)
+                .expect("an empty board contains every tile");
         }
 
         Ok(board)
@@ -305,6 +519,39 @@ pub struct MoveMessage {
 }
 
 impl MoveMessage {
+    /// Check that the move agrees with the board sent along with it, which shows the position
+    /// after the move: `source` has to be empty and `dest` has to hold a piece of `mover`, of the
+    /// promoted kind if any. Returns a description of the mismatch otherwise.
+    pub fn check_consistency(&self, mover: Color) -> Result<(), String> {
+        let tile = |position: Position| {
+            self.board
+                .tile(position)
+                .map_err(|error| format!("tile {:?} is invalid: {}", position, error))
+        };
+
+        if let Some(piece) = tile(self.source)? {
+            return Err(format!(
+                "source {:?} still holds {:?} after the move",
+                self.source, piece
+            ));
+        }
+        match tile(self.dest)? {
+            None => Err(format!(
+                "destination {:?} is empty after the move",
+                self.dest
+            )),
+            Some(piece) if piece.color != mover => Err(format!(
+                "destination {:?} holds {:?} instead of a piece of the mover",
+                self.dest, piece
+            )),
+            Some(piece) if self.promotion.is_some_and(|kind| kind != piece.kind) => Err(format!(
+                "destination {:?} holds {:?} instead of the promoted {:?}",
+                self.dest, piece, self.promotion
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+
     /// Serialize excluding message identifier and padding.
     fn serialize(&self) -> String {
         format!(
@@ -354,10 +601,10 @@ impl FromStr for MoveMessage {
         // Parse move
         let (source, dest, promotion) = match *move_str.chars().collect::<Box<[char]>>() {
             [
-                source_column_char @ 'A'..='H',
-                source_row_char @ '1'..='9',
-                dest_column_char @ 'A'..='H',
-                dest_row_char @ '1'..='9',
+                source_column_char @ ('A'..='H' | 'a'..='h'),
+                source_row_char @ '1'..='8',
+                dest_column_char @ ('A'..='H' | 'a'..='h'),
+                dest_row_char @ '1'..='8',
                 promotion_char,
             ] => {
                 let Some(source) =
@@ -441,30 +688,113 @@ impl FromStr for QuitMessage {
     }
 }
 
+/// Returns the longest start of `text` which is at most `max_len` bytes, without splitting a
+/// character.
+fn truncate_at_char_boundary(text: &str, max_len: usize) -> &str {
+    let mut end = max_len.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Sent by a player who gives up, which ends the game without leaving it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ResignMessage {
+    /// The side which resigns.
+    pub color: Color,
+}
+
+impl ResignMessage {
+    /// Serialize excluding message identifier and padding.
+    pub fn serialize(&self) -> String {
+        self.color.to_fen_char().to_string()
+    }
+}
+
+impl FromStr for ResignMessage {
+    type Err = ParseError;
+
+    /// Parse from string, excluding the message identifier and first separator.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(":");
+
+        let color_str = parts.next().expect("Split returns at least one element");
+        let Some(_padding_str) = parts.next() else {
+            return Err(ParseError::TooFewParts(2));
+        };
+
+        let mut chars = color_str.chars();
+        match (chars.next().and_then(Color::from_fen_char), chars.next()) {
+            (Some(color), None) => Ok(Self { color }),
+            _ => Err(ParseError::InvalidColor(color_str.to_owned())),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Message {
     Move(MoveMessage),
     Quit(QuitMessage),
+    Resign(ResignMessage),
+}
+
+impl From<MoveMessage> for Message {
+    fn from(value: MoveMessage) -> Self {
+        Self::Move(value)
+    }
+}
+
+impl From<QuitMessage> for Message {
+    fn from(value: QuitMessage) -> Self {
+        Self::Quit(value)
+    }
+}
+
+impl From<ResignMessage> for Message {
+    fn from(value: ResignMessage) -> Self {
+        Self::Resign(value)
+    }
 }
 
 impl Message {
     pub fn parse_from(buffer: &[u8; 128]) -> Result<Self, ParseError> {
+        Self::parse_from_with_prefix(buffer, DEFAULT_PREFIX)
+    }
+
+    /// Like `parse_from`, but for a protocol variant whose message identifiers start with `prefix`
+    /// instead of `DEFAULT_PREFIX`, e.g. "XMOVE" for the prefix "X".
+    pub fn parse_from_with_prefix(buffer: &[u8; 128], prefix: &str) -> Result<Self, ParseError> {
         let message = split_message(buffer)?;
 
-        match message.identifier {
-            "ChessMOVE" => Ok(Self::Move(message.rest.parse()?)),
-            "ChessQUIT" => Ok(Self::Quit(message.rest.parse()?)),
+        match message.identifier.strip_prefix(prefix) {
+            Some("MOVE") => Ok(Self::Move(message.rest.parse()?)),
+            Some("QUIT") => Ok(Self::Quit(message.rest.parse()?)),
+            Some("RESIGN") => Ok(Self::Resign(message.rest.parse()?)),
             _ => Err(ParseError::InvalidMessageId(message.identifier.to_owned())),
         }
     }
 
     pub fn serialize(&self) -> [u8; 128] {
+        self.serialize_with_prefix(DEFAULT_PREFIX)
+    }
+
+    /// Like `serialize`, but starts the message identifier with `prefix` instead of
+    /// `DEFAULT_PREFIX`. The prefix may be at most `MAX_PREFIX_LEN` bytes.
+    pub fn serialize_with_prefix(&self, prefix: &str) -> [u8; 128] {
         let (id, message) = match self {
-            Self::Move(message) => ("ChessMOVE", message.serialize()),
-            Self::Quit(message) => ("ChessQUIT", message.serialize()),
+            Self::Move(message) => ("MOVE", message.serialize()),
+            // Long quit messages are cut off so that the frame still ends with a separator. The
+            // other messages always fit.
+            Self::Quit(message) => (
+                "QUIT",
+                truncate_at_char_boundary(&message.serialize(), max_quit_message_len(prefix))
+                    .to_owned(),
+            ),
+            Self::Resign(message) => ("RESIGN", message.serialize()),
         };
 
-        let result_without_padding = format!("{}:{}:", id, message);
+        let result_without_padding = format!("{}{}:{}:", prefix, id, message);
 
         let mut buffer = ['0' as u8; 128];
         buffer[0..result_without_padding.len()].copy_from_slice(result_without_padding.as_bytes());
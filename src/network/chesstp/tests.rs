@@ -174,6 +174,32 @@ fn message_parse_move_promotion() {
     )
 }
 
+#[test]
+fn message_parse_move_lowercase() {
+    let message_bytes = b"ChessMOVE:e2e40:0-0:rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR:0000000000000000000000000000000000000000000000000000000000000000";
+
+    assert_eq!(
+        Message::parse_from(message_bytes),
+        Ok(Message::Move(MoveMessage {
+            source: Position::parse("e2").unwrap(),
+            dest: Position::parse("e4").unwrap(),
+            promotion: None,
+            phase: GamePhase::Ongoing,
+            board: chess::game::game_state::new().into()
+        }))
+    )
+}
+
+#[test]
+fn message_parse_move_rank_nine() {
+    let message_bytes = b"ChessMOVE:E2E90:0-0:rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR:0000000000000000000000000000000000000000000000000000000000000000";
+
+    assert_eq!(
+        Message::parse_from(message_bytes),
+        Err(ParseError::InvalidMove("E2E90".to_owned()))
+    )
+}
+
 #[test]
 fn message_serialize_move_ongoing() {
     let message = Message::Move(MoveMessage {
@@ -256,3 +282,374 @@ fn message_serialize_quit_unicode() {
 
     assert_eq!(Message::parse_from(&message.serialize()), Ok(message),)
 }
+
+#[test]
+fn message_serialize_quit_too_long() {
+    // 'ë' is two bytes and the message has room for 117, so the 59th 'ë' would be split.
+    let message = Message::Quit(QuitMessage {
+        message: "ë".repeat(100),
+    });
+
+    let frame = message.serialize();
+    assert_eq!(&frame[126..], b":0");
+    assert_eq!(
+        Message::parse_from(&frame),
+        Ok(Message::Quit(QuitMessage {
+            message: "ë".repeat(58),
+        }))
+    );
+}
+
+#[test]
+fn board_count_standard() {
+    let board: Board = chess::game::game_state::new().into();
+
+    assert_eq!(
+        board.count(Piece {
+            kind: PieceKind::Pawn,
+            color: Color::White
+        }),
+        8
+    );
+    assert_eq!(
+        board.count(Piece {
+            kind: PieceKind::King,
+            color: Color::Black
+        }),
+        1
+    );
+}
+
+#[test]
+fn board_count_empty() {
+    assert_eq!(
+        Board::new_empty().count(Piece {
+            kind: PieceKind::King,
+            color: Color::White
+        }),
+        0
+    );
+}
+
+#[test]
+fn board_validate_standard() {
+    let board: Board = chess::game::game_state::new().into();
+
+    assert_eq!(board.validate(), Ok(()));
+}
+
+#[test]
+fn board_validate_missing_king() {
+    let board = "rnbq1bnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR"
+        .parse::<Board>()
+        .unwrap();
+
+    assert_eq!(
+        board.validate(),
+        Err(BoardValidationError::KingCount(Color::Black, 0))
+    );
+}
+
+#[test]
+fn board_validate_extra_king() {
+    let board = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBKKBNR"
+        .parse::<Board>()
+        .unwrap();
+
+    assert_eq!(
+        board.validate(),
+        Err(BoardValidationError::KingCount(Color::White, 2))
+    );
+}
+
+#[test]
+fn board_validate_too_many_promoted() {
+    let board = "4k3/8/8/8/8/8/PPPPPPPP/QQ2K3".parse::<Board>().unwrap();
+
+    assert_eq!(
+        board.validate(),
+        Err(BoardValidationError::TooManyPromotedPieces(Color::White))
+    );
+}
+
+#[test]
+fn board_tile_short_board() {
+    let mut board = Board::new_empty();
+    board.board.truncate(3);
+    let position = Position::parse("a3").unwrap();
+
+    assert_eq!(board.tile(position), Err(TileError::OutOfBounds));
+    assert_eq!(board.set_tile(position, None), Err(TileError::OutOfBounds));
+    assert_eq!(
+        board.validate(),
+        Err(BoardValidationError::MalformedTile(position))
+    );
+}
+
+#[test]
+fn board_tile_garbled() {
+    let mut board = Board::new_empty();
+    let position = Position::parse("c2").unwrap();
+    board.board[2][3] = "dragon".to_owned();
+    board.player[2][3] = 'w';
+
+    assert_eq!(
+        board.tile(position),
+        Err(TileError::InvalidPiece("dragon".to_owned()))
+    );
+    assert_eq!(board.serialize(), "8/8/8/8/8/8/2?5/8");
+    assert_eq!(
+        board.validate(),
+        Err(BoardValidationError::MalformedTile(position))
+    );
+
+    board.board[2][3] = "pawn".to_owned();
+    board.player[2][3] = 'x';
+    assert_eq!(board.tile(position), Err(TileError::InvalidPlayer('x')));
+}
+
+#[test]
+fn parse_error_display_invalid_tile() {
+    let message_bytes = b"ChessMOVE:E2E40:0-0:rnbqkbnr/pppppppp/8/8/8/8/PPPPxPPP/RNBQKBNR:0000000000000000000000000000000000000000000000000000000000000000";
+
+    let error = Message::parse_from(message_bytes).unwrap_err();
+    assert_eq!(error.to_string(), "malformed board: invalid tile 'x'");
+}
+
+// Golden frames, byte for byte, which other chesstp implementations send and expect. Every frame is
+// exactly 128 bytes of ASCII (UTF-8 in quit messages), laid out as
+//
+//     ChessMOVE:<source><dest><promotion>:<phase>:<board>:<padding>
+//     ChessQUIT:<message>:<padding>
+//
+// where squares are uppercase ("E2"), promotion is a lowercase piece letter or '0' for none, phase
+// is "0-0" (ongoing), "1-0" (white won), "0-1" (black won) or "1-1" (draw), board is the piece
+// placement part of FEN from rank 8 down, and padding is '0' repeated up to 128 bytes.
+
+fn move_message(
+    source: &str,
+    dest: &str,
+    promotion: Option<PieceKind>,
+    phase: GamePhase,
+    board: &str,
+) -> Message {
+    Message::Move(MoveMessage {
+        source: Position::parse(source).unwrap(),
+        dest: Position::parse(dest).unwrap(),
+        promotion,
+        phase,
+        board: board.parse().unwrap(),
+    })
+}
+
+// Ongoing move (1. e4).
+#[test]
+fn golden_frame_move() {
+    let frame = b"ChessMOVE:E2E40:0-0:rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR:00000000000000000000000000000000000000000000000000000000000000";
+    let message = move_message(
+        "e2",
+        "e4",
+        None,
+        GamePhase::Ongoing,
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR",
+    );
+
+    assert_eq!(&message.serialize(), frame);
+    assert_eq!(Message::parse_from(frame), Ok(message));
+}
+
+// Black wins by checkmate (fool's mate).
+#[test]
+fn golden_frame_win() {
+    let frame = b"ChessMOVE:D8H40:0-1:rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR:00000000000000000000000000000000000000000000000000000000000";
+    let message = move_message(
+        "d8",
+        "h4",
+        None,
+        GamePhase::Win(Color::Black),
+        "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR",
+    );
+
+    assert_eq!(&message.serialize(), frame);
+    assert_eq!(Message::parse_from(frame), Ok(message));
+}
+
+// Draw by stalemate.
+#[test]
+fn golden_frame_draw() {
+    let frame = b"ChessMOVE:D7C70:1-1:k7/2Q5/1K6/8/8/8/8/8:000000000000000000000000000000000000000000000000000000000000000000000000000000000000000";
+    let message = move_message("d7", "c7", None, GamePhase::Draw, "k7/2Q5/1K6/8/8/8/8/8");
+
+    assert_eq!(&message.serialize(), frame);
+    assert_eq!(Message::parse_from(frame), Ok(message));
+}
+
+// Underpromotion to a knight.
+#[test]
+fn golden_frame_promotion() {
+    let frame = b"ChessMOVE:A7A8n:0-0:N3k3/8/8/8/8/8/8/4K3:000000000000000000000000000000000000000000000000000000000000000000000000000000000000000";
+    let message = move_message(
+        "a7",
+        "a8",
+        Some(PieceKind::Knight),
+        GamePhase::Ongoing,
+        "N3k3/8/8/8/8/8/8/4K3",
+    );
+
+    assert_eq!(&message.serialize(), frame);
+    assert_eq!(Message::parse_from(frame), Ok(message));
+}
+
+// Quit with a message.
+#[test]
+fn golden_frame_quit() {
+    let frame = b"ChessQUIT:Resignation:0000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000";
+    let message = Message::Quit(QuitMessage {
+        message: "Resignation".to_owned(),
+    });
+
+    assert_eq!(&message.serialize(), frame);
+    assert_eq!(Message::parse_from(frame), Ok(message));
+}
+
+#[test]
+fn custom_prefix_round_trip() {
+    let message = Message::Quit(QuitMessage {
+        message: "Resignation".to_owned(),
+    });
+    let frame = message.serialize_with_prefix("Sync");
+
+    assert!(frame.starts_with(b"SyncQUIT:Resignation:"));
+    assert_eq!(Message::parse_from_with_prefix(&frame, "Sync"), Ok(message));
+    assert_eq!(
+        Message::parse_from(&frame),
+        Err(ParseError::InvalidMessageId("SyncQUIT".to_owned()))
+    );
+}
+
+#[test]
+fn longest_move_fits_longest_prefix() {
+    let prefix = "X".repeat(MAX_PREFIX_LEN);
+    let message = Message::Move(MoveMessage {
+        source: Position::parse("a7").unwrap(),
+        dest: Position::parse("a8").unwrap(),
+        promotion: Some(PieceKind::Queen),
+        phase: GamePhase::Win(Color::White),
+        board: "rnbqkbnr/pppppppp/rnbqkbnr/pppppppp/PPPPPPPP/RNBQKBNR/PPPPPPPP/RNBQKBNR"
+            .parse()
+            .unwrap(),
+    });
+
+    let frame = message.serialize_with_prefix(&prefix);
+    assert_eq!(frame[127], b':');
+    assert_eq!(
+        Message::parse_from_with_prefix(&frame, &prefix),
+        Ok(message)
+    );
+}
+
+#[test]
+fn message_serialize_with_prefix_only_truncates_quit() {
+    let prefix = "X".repeat(MAX_PREFIX_LEN);
+    let message = Message::Quit(QuitMessage {
+        message: "a".repeat(200),
+    });
+
+    let frame = message.serialize_with_prefix(&prefix);
+    assert_eq!(
+        Message::parse_from_with_prefix(&frame, &prefix),
+        Ok(Message::Quit(QuitMessage {
+            message: "a".repeat(max_quit_message_len(&prefix)),
+        }))
+    );
+}
+
+fn check_consistency(
+    source: &str,
+    dest: &str,
+    promotion: Option<PieceKind>,
+    board: &str,
+) -> Result<(), String> {
+    let Message::Move(message) = move_message(source, dest, promotion, GamePhase::Ongoing, board)
+    else {
+        unreachable!("move_message creates move messages");
+    };
+    message.check_consistency(Color::White)
+}
+
+#[test]
+fn move_consistency_matching_board() {
+    assert_eq!(
+        check_consistency("e2", "e4", None, "4k3/8/8/8/4P3/8/8/4K3"),
+        Ok(())
+    );
+    assert_eq!(
+        check_consistency("a7", "a8", Some(PieceKind::Queen), "Q3k3/8/8/8/8/8/8/4K3"),
+        Ok(())
+    );
+}
+
+#[test]
+fn move_consistency_mismatched_board() {
+    // The source is still occupied.
+    assert!(check_consistency("e2", "e4", None, "4k3/8/8/8/8/8/4P3/4K3").is_err());
+    // The destination holds an opposing piece.
+    assert!(check_consistency("e2", "e4", None, "4k3/8/8/8/4p3/8/8/4K3").is_err());
+    // The destination holds another piece than the promotion.
+    assert!(check_consistency("a7", "a8", Some(PieceKind::Queen), "N3k3/8/8/8/8/8/8/4K3").is_err());
+}
+
+#[test]
+fn board_to_unicode_starting_position() {
+    let board = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR"
+        .parse::<Board>()
+        .unwrap();
+
+    assert_eq!(
+        board.to_unicode(),
+        "8 ♜ ♞ ♝ ♛ ♚ ♝ ♞ ♜\n\
+         7 ♟ ♟ ♟ ♟ ♟ ♟ ♟ ♟\n\
+         6 · · · · · · · ·\n\
+         5 · · · · · · · ·\n\
+         4 · · · · · · · ·\n\
+         3 · · · · · · · ·\n\
+         2 ♙ ♙ ♙ ♙ ♙ ♙ ♙ ♙\n\
+         1 ♖ ♘ ♗ ♕ ♔ ♗ ♘ ♖\n  \
+         a b c d e f g h"
+    );
+    // Every line has one glyph per tile, regardless of how wide terminals draw them.
+    for line in board.to_unicode().lines() {
+        assert_eq!(line.chars().count(), 17, "{:?}", line);
+    }
+}
+
+#[test]
+fn golden_frame_resign() {
+    let frame = b"ChessRESIGN:b:000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000";
+    let message = Message::Resign(ResignMessage {
+        color: Color::Black,
+    });
+
+    assert_eq!(&message.serialize(), frame);
+    assert_eq!(Message::parse_from(frame), Ok(message));
+}
+
+#[test]
+fn message_serialize_resign_white() {
+    let message = Message::Resign(ResignMessage {
+        color: Color::White,
+    });
+
+    assert_eq!(Message::parse_from(&message.serialize()), Ok(message));
+}
+
+#[test]
+fn message_parse_resign_invalid_color() {
+    let mut frame = [b'0'; 128];
+    frame[..17].copy_from_slice(b"ChessRESIGN:red:0");
+
+    assert_eq!(
+        Message::parse_from(&frame),
+        Err(ParseError::InvalidColor("red".to_owned()))
+    );
+}
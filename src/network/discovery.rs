@@ -0,0 +1,126 @@
+//! Discovery of servers on the local network. A server periodically broadcasts an announcement
+//! containing its name and port over UDP, which clients listen for during setup.
+
+use std::{
+    fmt::Display,
+    io,
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+/// UDP port which announcements are broadcast to.
+pub static DISCOVERY_PORT: u16 = 3001;
+/// How often a server broadcasts its announcement.
+static ANNOUNCE_INTERVAL: Duration = Duration::from_secs(1);
+/// Identifier at the start of every announcement, followed by the port and name, e.g.
+/// `ChessHOST:3000:Alice's game`.
+static ANNOUNCEMENT_PREFIX: &str = "ChessHOST";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredServer {
+    pub name: String,
+    /// The address to connect to, i.e. the announcing host together with the announced port.
+    pub addr: SocketAddr,
+}
+
+impl Display for DiscoveredServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.name, self.addr)
+    }
+}
+
+/// Name a server announces itself with, based on the current user's name.
+pub fn default_server_name() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .map(|user| format!("{}'s game", user))
+        .unwrap_or_else(|_| "Chess game".to_owned())
+}
+
+pub fn serialize_announcement(name: &str, port: u16) -> String {
+    format!("{}:{}:{}", ANNOUNCEMENT_PREFIX, port, name)
+}
+
+/// Parse announcement received from `sender`. Returns `None` if it isn't a valid announcement.
+pub fn parse_announcement(data: &[u8], sender: SocketAddr) -> Option<DiscoveredServer> {
+    let rest = std::str::from_utf8(data)
+        .ok()?
+        .strip_prefix(ANNOUNCEMENT_PREFIX)?
+        .strip_prefix(":")?;
+    let (port_str, name) = rest.split_once(":")?;
+
+    Some(DiscoveredServer {
+        name: name.to_owned(),
+        addr: SocketAddr::new(sender.ip(), port_str.parse().ok()?),
+    })
+}
+
+/// Periodically broadcasts the presence of a server.
+pub struct Announcer {
+    socket: UdpSocket,
+    announcement: String,
+    last_sent: Option<Instant>,
+}
+
+impl Announcer {
+    pub fn new(name: &str, port: u16) -> io::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        socket.set_broadcast(true)?;
+
+        Ok(Self {
+            socket,
+            announcement: serialize_announcement(name, port),
+            last_sent: None,
+        })
+    }
+
+    /// Broadcast the announcement if enough time has passed since the last one. Is meant to be
+    /// called in a loop while waiting for a connection.
+    pub fn tick(&mut self) -> io::Result<()> {
+        if self
+            .last_sent
+            .is_some_and(|last_sent| last_sent.elapsed() < ANNOUNCE_INTERVAL)
+        {
+            return Ok(());
+        }
+
+        self.socket.send_to(
+            self.announcement.as_bytes(),
+            (Ipv4Addr::BROADCAST, DISCOVERY_PORT),
+        )?;
+        self.last_sent = Some(Instant::now());
+
+        Ok(())
+    }
+}
+
+/// Listen for server announcements for the specified duration, returning every distinct server
+/// heard from.
+pub fn discover_servers(duration: Duration) -> io::Result<Vec<DiscoveredServer>> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, DISCOVERY_PORT))?;
+    socket.set_read_timeout(Some(Duration::from_millis(100)))?;
+
+    let deadline = Instant::now() + duration;
+    let mut servers = Vec::new();
+    let mut buffer = [0u8; 256];
+
+    while Instant::now() < deadline {
+        match socket.recv_from(&mut buffer) {
+            Ok((len, sender)) => {
+                if let Some(server) = parse_announcement(&buffer[..len], sender) {
+                    if !servers.contains(&server) {
+                        servers.push(server);
+                    }
+                }
+            }
+            Err(ref error)
+                if matches!(
+                    error.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(error) => return Err(error),
+        }
+    }
+
+    Ok(servers)
+}
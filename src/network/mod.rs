@@ -1,24 +1,85 @@
 use std::{
-    io::{self, BufRead, BufReader, Read, Write},
+    collections::VecDeque,
+    fmt::Display,
+    io::{self, BufRead, BufReader, Write},
     net::{self, SocketAddr, TcpStream},
+    time::{Duration, Instant},
 };
 
-use anyhow::anyhow;
-
 pub mod chesstp;
+pub mod discovery;
 pub mod setup;
 #[cfg(test)]
 mod tests;
 
+/// How long `ChesstpMessageStream::close` waits for queued messages to be sent.
+static CLOSE_FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConnectionType {
     Server,
     Client,
 }
 
+/// Whether a `ChesstpMessageStream` can still be used, see `ChesstpMessageStream::health`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionHealth {
+    Healthy,
+    /// The peer closed the connection, i.e. reading from it reached the end.
+    PeerClosed,
+    /// Sending to the peer failed while the read side still seemed open.
+    WriteFailed,
+    /// Reading from the peer failed with an error, e.g. because the connection was reset.
+    ReadFailed,
+    /// The peer stopped sending in the middle of a message, which was discarded. Clears once a
+    /// complete message arrives again.
+    Stalled,
+}
+
+/// Why sending or receiving chesstp messages failed.
+#[derive(Debug)]
+pub enum TransportError {
+    Io(io::Error),
+    /// The peer stopped sending for longer than the frame timeout in the middle of a message.
+    Stalled(Duration),
+    /// The connection was closed while a message was being written.
+    Disconnected,
+}
+
+impl Display for TransportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransportError::Io(error) => write!(f, "{}", error),
+            TransportError::Stalled(timeout) => write!(
+                f,
+                "Peer stalled for over {:?} in the middle of a message",
+                timeout
+            ),
+            TransportError::Disconnected => f.write_str("Connection closed while writing message"),
+        }
+    }
+}
+
+impl std::error::Error for TransportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TransportError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TransportError {
+    fn from(value: io::Error) -> Self {
+        TransportError::Io(value)
+    }
+}
+
 #[derive(Debug)]
 pub enum GameConnection {
     Local,
+    /// Local free exploration, where either color may move at any time.
+    Analysis,
     Remote(ConnectionType, SocketAddr, ChesstpMessageStream),
 }
 
@@ -26,58 +87,276 @@ pub enum GameConnection {
 pub struct ChesstpMessageStream {
     reader: BufReader<TcpStream>,
     writer: TcpStream,
+    /// The sync marker every frame starts with, since the message identifiers embed it.
+    prefix: String,
+    /// Serialized frames which haven't been completely written yet, since the socket is
+    /// non-blocking. The first frame may have been partially written, see `pending_offset`.
+    pending: VecDeque<[u8; 128]>,
+    /// How many bytes of the first pending frame have already been written.
+    pending_offset: usize,
+    /// The bytes read so far of a frame which has been started but not yet completely received.
+    partial_frame: Vec<u8>,
+    /// When the prefix of the currently partially received frame was found.
+    frame_started_at: Option<Instant>,
+    /// How long a started frame may stay incomplete before `accept` gives up on it.
+    frame_timeout: Option<Duration>,
+    /// Set once reading reached the end of the stream.
+    peer_closed: bool,
+    /// Set once writing failed with an error other than would block.
+    write_failed: bool,
+    /// Set once reading failed with an error other than would block.
+    read_failed: bool,
+    /// Set when a started frame timed out, until the next complete frame is received.
+    stalled: bool,
+    /// Why the last malformed frame was discarded, until taken by `take_discarded_frame_error`.
+    discarded_frame_error: Option<chesstp::ParseError>,
+    /// Set once `close` has shut the connection down.
+    closed: bool,
 }
 
 impl ChesstpMessageStream {
-    pub fn new(stream: TcpStream) -> anyhow::Result<Self> {
+    pub fn new(stream: TcpStream) -> Result<Self, TransportError> {
+        Self::with_prefix(stream, chesstp::DEFAULT_PREFIX)
+    }
+
+    /// Like `ChesstpMessageStream::new`, but for a protocol variant which uses `prefix` as the sync
+    /// marker instead of `chesstp::DEFAULT_PREFIX`. Assumes that the message identifiers consist of
+    /// the prefix followed by "MOVE", "QUIT" or "RESIGN", so it may not be empty or contain ':'.
+    /// It may also be at most `chesstp::MAX_PREFIX_LEN` bytes, so that every message fits a frame.
+    pub fn with_prefix(stream: TcpStream, prefix: &str) -> Result<Self, TransportError> {
+        if prefix.is_empty() || prefix.contains(':') || prefix.len() > chesstp::MAX_PREFIX_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid frame prefix {:?}", prefix),
+            )
+            .into());
+        }
+
         stream.set_nonblocking(true)?;
         let reader = BufReader::new(stream.try_clone()?);
 
         Ok(Self {
             reader,
             writer: stream,
+            prefix: prefix.to_owned(),
+            pending: VecDeque::new(),
+            pending_offset: 0,
+            partial_frame: Vec::with_capacity(128),
+            frame_started_at: None,
+            frame_timeout: None,
+            peer_closed: false,
+            write_failed: false,
+            read_failed: false,
+            stalled: false,
+            discarded_frame_error: None,
+            closed: false,
         })
     }
 
+    /// Set how long the peer may stall in the middle of sending a message before `accept` returns
+    /// an error. `None` (the default) waits indefinitely.
+    pub fn set_frame_timeout(&mut self, timeout: Option<Duration>) {
+        self.frame_timeout = timeout;
+    }
+
     /// Read chesstp message from connection, returning `None` if there isn't enough data available
     /// yet. Is meant to be called in a loop, only returning a message occasionally.
-    pub fn accept(&mut self) -> anyhow::Result<Option<chesstp::Message>> {
-        if skip_until_slice(&mut self.reader, b"Chess")?.is_none() {
-            // Couldn't find prefix yet.
-            return Ok(None);
+    ///
+    /// Malformed frames aren't fatal: they're logged and skipped up to the next frame prefix.
+    /// Errors are also recorded in `health`.
+    pub fn accept(&mut self) -> Result<Option<chesstp::Message>, TransportError> {
+        let result = self.accept_frame();
+        match result {
+            Ok(Some(_)) => self.stalled = false,
+            Ok(None) => {}
+            Err(TransportError::Stalled(_)) => self.stalled = true,
+            Err(_) => self.read_failed = true,
         }
+        result
+    }
 
-        let mut message_buf = [0u8; 128];
-        match self.reader.read_exact(&mut message_buf) {
-            Ok(_) => {}
-            Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => {
+    fn accept_frame(&mut self) -> Result<Option<chesstp::Message>, TransportError> {
+        loop {
+            match self.reader.fill_buf() {
+                Ok(buf) if buf.is_empty() => {
+                    // The peer won't send anything more.
+                    self.peer_closed = true;
+                    return Ok(None);
+                }
+                Ok(_) => {}
+                Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => {}
+                Err(error) => return Err(error.into()),
+            }
+
+            if self.frame_started_at.is_none() {
+                if skip_until_slice(&mut self.reader, self.prefix.as_bytes())?.is_none() {
+                    // Couldn't find prefix yet.
+                    return Ok(None);
+                }
+                self.frame_started_at = Some(Instant::now());
+            }
+
+            while self.partial_frame.len() < 128 {
+                let buf = match self.reader.fill_buf() {
+                    Ok(buf) => buf,
+                    Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(error) => return Err(error.into()),
+                };
+                if buf.is_empty() {
+                    break;
+                }
+
+                let read_len = buf.len().min(128 - self.partial_frame.len());
+                self.partial_frame.extend_from_slice(&buf[..read_len]);
+                self.reader.consume(read_len);
+            }
+
+            if self.partial_frame.len() < 128 {
                 // There isn't enough data to read currently.
+                if let (Some(timeout), Some(started_at)) =
+                    (self.frame_timeout, self.frame_started_at)
+                {
+                    if started_at.elapsed() > timeout {
+                        self.partial_frame.clear();
+                        self.frame_started_at = None;
+                        return Err(TransportError::Stalled(timeout));
+                    }
+                }
                 return Ok(None);
             }
-            Err(error) => return Err(error.into()),
-        };
-        match chesstp::Message::parse_from(&message_buf) {
-            Ok(message) => Ok(Some(message)),
-            Err(error) => Err(anyhow!("Couldn't parse message: {:?}", error)),
+
+            let mut message_buf = [0u8; 128];
+            message_buf.copy_from_slice(&self.partial_frame);
+
+            match chesstp::Message::parse_from_with_prefix(&message_buf, &self.prefix) {
+                Ok(message) => {
+                    self.partial_frame.clear();
+                    self.frame_started_at = None;
+                    return Ok(Some(message));
+                }
+                Err(error) => {
+                    // Resynchronize on the next prefix, which may already have been read as part
+                    // of this frame.
+                    let resume_at = next_frame_start(&self.partial_frame, self.prefix.as_bytes())
+                        .unwrap_or(self.partial_frame.len());
+                    println!(
+                        "Discarding malformed frame ({:?}): {:?}",
+                        error,
+                        String::from_utf8_lossy(&self.partial_frame[..resume_at])
+                    );
+                    self.partial_frame.drain(..resume_at);
+                    self.discarded_frame_error = Some(error);
+                    self.frame_started_at = if self.partial_frame.is_empty() {
+                        None
+                    } else {
+                        Some(Instant::now())
+                    };
+                }
+            }
         }
     }
 
-    pub fn write(&mut self, message: chesstp::Message) -> anyhow::Result<()> {
-        let written_len = self.writer.write(&message.serialize())?;
+    /// Queue message to be sent and try to send as much of the queue as possible right away. Any
+    /// part which couldn't be written without blocking is sent by later calls to `flush_pending`.
+    pub fn write(&mut self, message: chesstp::Message) -> Result<(), TransportError> {
+        self.pending
+            .push_back(message.serialize_with_prefix(&self.prefix));
+        self.flush_pending()
+    }
 
-        if written_len != 128 {
-            return Err(anyhow!("Could only read {} bytes of message", written_len));
+    /// Write queued frames until the queue is empty or the socket would block. Is meant to be
+    /// called frequently, e.g. every update, so that queued messages are eventually sent.
+    pub fn flush_pending(&mut self) -> Result<(), TransportError> {
+        while let Some(frame) = self.pending.front() {
+            match self.writer.write(&frame[self.pending_offset..]) {
+                Ok(0) => {
+                    self.write_failed = true;
+                    return Err(TransportError::Disconnected);
+                }
+                Ok(written_len) => {
+                    self.pending_offset += written_len;
+                    if self.pending_offset == frame.len() {
+                        self.pending.pop_front();
+                        self.pending_offset = 0;
+                    }
+                }
+                Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => {
+                    // The socket buffer is full, try again next time.
+                    return Ok(());
+                }
+                Err(ref error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => {
+                    self.write_failed = true;
+                    return Err(error.into());
+                }
+            }
         }
 
         Ok(())
     }
 
-    pub fn close(&mut self) -> anyhow::Result<()> {
+    /// Returns whether the connection still works, distinguishing the peer leaving from failing to
+    /// send to them. The peer leaving takes precedence, since writes usually fail after that too.
+    /// Is only updated by `accept` and writing.
+    pub fn health(&self) -> ConnectionHealth {
+        if self.peer_closed {
+            ConnectionHealth::PeerClosed
+        } else if self.read_failed {
+            ConnectionHealth::ReadFailed
+        } else if self.write_failed {
+            ConnectionHealth::WriteFailed
+        } else if self.stalled {
+            ConnectionHealth::Stalled
+        } else {
+            ConnectionHealth::Healthy
+        }
+    }
+
+    /// Returns why the last malformed frame was skipped by `accept`, if one has been since the
+    /// last call, so that it can be shown to the user.
+    pub fn take_discarded_frame_error(&mut self) -> Option<chesstp::ParseError> {
+        self.discarded_frame_error.take()
+    }
+
+    /// Whether there are queued messages which haven't been completely sent yet.
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Shut the connection down, after trying to send everything still queued for up to
+    /// `CLOSE_FLUSH_TIMEOUT`, so that e.g. a quit message written right before still arrives.
+    /// Closing an already closed stream does nothing.
+    pub fn close(&mut self) -> Result<(), TransportError> {
+        if self.closed {
+            return Ok(());
+        }
+
+        let started_at = Instant::now();
+        while self.has_pending() && started_at.elapsed() < CLOSE_FLUSH_TIMEOUT {
+            if let Err(error) = self.flush_pending() {
+                println!("Couldn't send queued messages before closing: {}", error);
+                break;
+            }
+            if self.has_pending() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+
         self.writer.shutdown(net::Shutdown::Both)?;
+        self.closed = true;
         Ok(())
     }
 }
 
+/// Returns the index of the first place after the start of `frame` where a new frame could start,
+/// i.e. where `prefix` is found, or the beginning of it is found at the very end.
+fn next_frame_start(frame: &[u8], prefix: &[u8]) -> Option<usize> {
+    (1..frame.len()).find(|&start| {
+        let candidate = &frame[start..frame.len().min(start + prefix.len())];
+        prefix.starts_with(candidate)
+    })
+}
+
 /// Consume BufRead until slice has been found, without consuming the slice. Returns None if the
 /// operation would block while the underlying buffer was configured to be non-blocking.
 pub fn skip_until_slice(reader: &mut impl BufRead, slice: &[u8]) -> io::Result<Option<()>> {
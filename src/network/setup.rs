@@ -3,12 +3,27 @@
 
 use std::{
     fmt::Display,
-    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, TcpListener, TcpStream},
+    time::Duration,
 };
 
+use inquire::validator::Validation;
+
+use crate::network::discovery::{self, DiscoveredServer};
+
+#[cfg(test)]
+mod tests;
+
+/// How long to listen for server announcements before letting the user pick one.
+static DISCOVERY_DURATION: Duration = Duration::from_secs(2);
+
 #[derive(Debug, Copy, Clone)]
 pub enum NetworkMode {
     Local,
+    HotSeat,
+    Analysis,
+    Editor,
     Client,
     Server,
 }
@@ -17,6 +32,9 @@ impl Display for NetworkMode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(match self {
             NetworkMode::Local => "Local",
+            NetworkMode::HotSeat => "Local (hot-seat, board turns every move)",
+            NetworkMode::Analysis => "Analysis (either side may move)",
+            NetworkMode::Editor => "Local (board editor)",
             NetworkMode::Client => "Client",
             NetworkMode::Server => "Server",
         })
@@ -26,30 +44,197 @@ impl Display for NetworkMode {
 #[derive(Debug, Copy, Clone)]
 pub enum NetworkConfig {
     Local,
+    /// Local game where the board turns to face the side to move.
+    HotSeat,
+    /// Local exploration where either color may move at any time.
+    Analysis,
+    /// Local game starting from a position constructed in the board editor.
+    Editor,
     /// Will connect to a TCP server on the specified address.
     Client(SocketAddr),
-    /// Will setup TCP server on the specified port.
-    Server(u16),
+    /// Will setup TCP server on the specified port, listening on the specified interface or on all
+    /// interfaces if `None`.
+    Server(u16, Option<IpAddr>),
+}
+
+/// Server to connect to, as picked from the list of discovered servers.
+#[derive(Debug, Clone)]
+enum ClientTarget {
+    Discovered(DiscoveredServer),
+    Manual,
+}
+
+impl Display for ClientTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientTarget::Discovered(server) => write!(f, "{}", server),
+            ClientTarget::Manual => f.write_str("Enter address manually"),
+        }
+    }
+}
+
+/// Bind non-blocking listeners for a server on `port`, on `interface` or else on all interfaces.
+/// Listening on all interfaces covers both IPv4 and IPv6, falling back to only IPv4 where IPv6
+/// isn't available.
+pub fn bind_server(port: u16, interface: Option<IpAddr>) -> io::Result<Vec<TcpListener>> {
+    let listeners = match interface {
+        Some(interface) => vec![TcpListener::bind(SocketAddr::new(interface, port))?],
+        None => bind_dual_stack(port)?,
+    };
+    for listener in &listeners {
+        listener.set_nonblocking(true)?;
+    }
+
+    Ok(listeners)
+}
+
+/// Bind listeners on `port` of every IPv6 and IPv4 interface.
+fn bind_dual_stack(port: u16) -> io::Result<Vec<TcpListener>> {
+    let listener_v6 = match TcpListener::bind(SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), port)) {
+        Ok(listener) => listener,
+        Err(error) => {
+            println!("Could not listen on IPv6, using only IPv4: {}", error);
+            let addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port);
+            return Ok(vec![TcpListener::bind(addr)?]);
+        }
+    };
+
+    // Use the same port for IPv4, also when the OS picked it since `port` was 0.
+    let port = listener_v6.local_addr()?.port();
+    match TcpListener::bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port)) {
+        Ok(listener_v4) => Ok(vec![listener_v6, listener_v4]),
+        // Where IPv6 sockets accept IPv4 connections too, like by default on Linux, the IPv6
+        // listener already holds the IPv4 port.
+        Err(error) if error.kind() == io::ErrorKind::AddrInUse => Ok(vec![listener_v6]),
+        Err(error) => Err(error),
+    }
+}
+
+/// Accept a connection from whichever of the non-blocking `listeners` has one waiting, or return
+/// `None` if none does. Peers connecting over IPv4 to an IPv6 listener are reported with their
+/// IPv4 address.
+pub fn accept_any(listeners: &[TcpListener]) -> io::Result<Option<(TcpStream, SocketAddr)>> {
+    for listener in listeners {
+        match listener.accept() {
+            Ok((stream, addr)) => {
+                return Ok(Some((
+                    stream,
+                    SocketAddr::new(addr.ip().to_canonical(), addr.port()),
+                )));
+            }
+            Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => {}
+            Err(error) => return Err(error),
+        }
+    }
+
+    Ok(None)
+}
+
+/// Connect to the server at `addr`, giving each of up to `attempts` attempts `timeout` to succeed.
+/// Waits `backoff` before the second attempt, and twice as long before each one after that, so that
+/// a server which is still starting up has time to. Returns the error of the last attempt if all
+/// fail.
+pub fn connect_with_retries(
+    addr: SocketAddr,
+    timeout: Duration,
+    attempts: u32,
+    backoff: Duration,
+) -> io::Result<TcpStream> {
+    let mut delay = backoff;
+    let mut attempt = 1;
+    loop {
+        println!(
+            "Connecting to {} (attempt {}/{})...",
+            addr, attempt, attempts
+        );
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(error) if attempt < attempts => {
+                println!("Could not connect: {}, retrying in {:?}", error, delay);
+                std::thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// Parse the address of a server to connect to, tolerating surrounding whitespace and a `tcp://`
+/// scheme since addresses are often pasted from elsewhere.
+pub fn parse_client_addr(string: &str) -> Result<SocketAddr, String> {
+    let string = string.trim();
+    let addr = match string.split_once("://") {
+        Some((scheme, addr)) if scheme.eq_ignore_ascii_case("tcp") => addr,
+        Some((scheme, _)) => {
+            return Err(format!(
+                "Unsupported scheme \"{}://\", only tcp:// is supported.",
+                scheme
+            ));
+        }
+        None => string,
+    };
+
+    addr.parse()
+        .map_err(|_| "Please type a valid IP and port.".to_owned())
 }
 
 pub fn prompt_network_config() -> Result<NetworkConfig, inquire::InquireError> {
     let mode = inquire::Select::new(
         "Game setup:",
-        vec![NetworkMode::Local, NetworkMode::Client, NetworkMode::Server],
+        vec![
+            NetworkMode::Local,
+            NetworkMode::HotSeat,
+            NetworkMode::Analysis,
+            NetworkMode::Editor,
+            NetworkMode::Client,
+            NetworkMode::Server,
+        ],
     )
     .prompt()?;
 
     match mode {
         NetworkMode::Local => Ok(NetworkConfig::Local),
+        NetworkMode::HotSeat => Ok(NetworkConfig::HotSeat),
+        NetworkMode::Analysis => Ok(NetworkConfig::Analysis),
+        NetworkMode::Editor => Ok(NetworkConfig::Editor),
         NetworkMode::Client => {
-            let addr = inquire::CustomType::<SocketAddr>::new("IP address to connect to:")
-                .with_placeholder("0.0.0.0:3000")
-                .with_parser(&|string| string.parse().map_err(|_| ()))
-                .with_error_message("Please type a valid IP and port.")
-                .with_default(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 3000).into())
+            println!("Searching for servers on the local network...");
+            let servers = discovery::discover_servers(DISCOVERY_DURATION).unwrap_or_else(|error| {
+                println!("Server discovery failed: {}", error);
+                Vec::new()
+            });
+
+            if !servers.is_empty() {
+                let options = servers
+                    .into_iter()
+                    .map(ClientTarget::Discovered)
+                    .chain([ClientTarget::Manual])
+                    .collect();
+
+                match inquire::Select::new("Server to connect to:", options).prompt()? {
+                    ClientTarget::Discovered(server) => {
+                        return Ok(NetworkConfig::Client(server.addr));
+                    }
+                    ClientTarget::Manual => {}
+                }
+            }
+
+            let default_addr = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 3000).to_string();
+            let addr = inquire::Text::new("IP address to connect to:")
+                .with_placeholder(&default_addr)
+                .with_default(&default_addr)
+                .with_validator(|string: &str| {
+                    Ok(match parse_client_addr(string) {
+                        Ok(_) => Validation::Valid,
+                        Err(error) => Validation::Invalid(error.into()),
+                    })
+                })
                 .prompt()?;
 
-            Ok(NetworkConfig::Client(addr))
+            Ok(NetworkConfig::Client(
+                parse_client_addr(&addr).expect("address was validated by the prompt"),
+            ))
         }
         NetworkMode::Server => {
             let port = inquire::CustomType::<u16>::new("Port to listen on:")
@@ -59,7 +244,18 @@ pub fn prompt_network_config() -> Result<NetworkConfig, inquire::InquireError> {
                 .with_default(3000)
                 .prompt()?;
 
-            Ok(NetworkConfig::Server(port))
+            let interface = inquire::CustomType::<IpAddr>::new("Interface to listen on:")
+                .with_placeholder("0.0.0.0")
+                .with_parser(&|string| string.parse().map_err(|_| ()))
+                .with_error_message("Please type a valid IP, e.g. 127.0.0.1 for localhost only.")
+                .with_help_message("0.0.0.0 listens on all interfaces")
+                .with_default(Ipv4Addr::UNSPECIFIED.into())
+                .prompt()?;
+
+            Ok(NetworkConfig::Server(
+                port,
+                Some(interface).filter(|interface| !interface.is_unspecified()),
+            ))
         }
     }
 }
@@ -0,0 +1,96 @@
+use super::*;
+
+#[test]
+fn parse_client_addr_plain() {
+    assert_eq!(
+        parse_client_addr("1.2.3.4:3000"),
+        Ok("1.2.3.4:3000".parse().unwrap())
+    );
+}
+
+#[test]
+fn parse_client_addr_tcp_scheme_and_whitespace() {
+    assert_eq!(
+        parse_client_addr("  tcp://1.2.3.4:3000\n"),
+        Ok("1.2.3.4:3000".parse().unwrap())
+    );
+    assert_eq!(
+        parse_client_addr("TCP://[::1]:3000"),
+        Ok("[::1]:3000".parse().unwrap())
+    );
+}
+
+#[test]
+fn parse_client_addr_other_scheme() {
+    let error = parse_client_addr("http://1.2.3.4:3000").unwrap_err();
+
+    assert!(error.contains("http://"));
+}
+
+/// Accept a connection from `listeners`, waiting for up to a second for one to arrive.
+fn accept_within_second(listeners: &[TcpListener]) -> Option<(TcpStream, SocketAddr)> {
+    let started_at = std::time::Instant::now();
+    while started_at.elapsed() < Duration::from_secs(1) {
+        if let Some(pair) = accept_any(listeners).unwrap() {
+            return Some(pair);
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    None
+}
+
+#[test]
+fn bind_server_all_interfaces_accepts_ipv4() {
+    let listeners = bind_server(0, None).unwrap();
+    let port = listeners[0].local_addr().unwrap().port();
+
+    // Whether or not IPv6 is available, IPv4 clients have to get through.
+    let client = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+    let (_, peer_addr) = accept_within_second(&listeners).unwrap();
+
+    assert_eq!(peer_addr, client.local_addr().unwrap());
+}
+
+#[test]
+fn bind_server_on_interface() {
+    let listeners = bind_server(0, Some(Ipv4Addr::LOCALHOST.into())).unwrap();
+
+    assert_eq!(listeners.len(), 1);
+    assert_eq!(accept_any(&listeners).unwrap().map(|(_, addr)| addr), None);
+}
+
+#[test]
+fn connect_with_retries_backs_off() {
+    // Nothing listens on the port once the listener is dropped.
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let started_at = std::time::Instant::now();
+    let result = connect_with_retries(
+        addr,
+        Duration::from_millis(100),
+        3,
+        Duration::from_millis(50),
+    );
+
+    assert!(result.is_err());
+    // Waited 50 ms before the second attempt and 100 ms before the third.
+    assert!(started_at.elapsed() >= Duration::from_millis(150));
+}
+
+#[test]
+fn connect_with_retries_first_attempt() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+    let stream = connect_with_retries(
+        listener.local_addr().unwrap(),
+        Duration::from_secs(1),
+        1,
+        Duration::from_secs(10),
+    )
+    .unwrap();
+
+    assert_eq!(stream.peer_addr().unwrap(), listener.local_addr().unwrap());
+}
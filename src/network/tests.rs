@@ -1,3 +1,5 @@
+use std::io::Read;
+
 use super::*;
 
 #[test]
@@ -36,3 +38,219 @@ fn read_until_slice_repeated() {
     reader.read_to_end(&mut result).unwrap();
     assert_eq!(result.as_slice(), b"Prefix",)
 }
+
+/// Create a connected pair of streams over the loopback interface.
+fn loopback_pair() -> (TcpStream, TcpStream) {
+    let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (server, _) = listener.accept().unwrap();
+
+    (client, server)
+}
+
+#[test]
+fn write_queues_multiple_messages() {
+    let (client, mut server) = loopback_pair();
+    let mut stream = ChesstpMessageStream::new(client).unwrap();
+
+    let messages = ["first", "second", "third"].map(|message| {
+        chesstp::Message::Quit(chesstp::QuitMessage {
+            message: message.to_owned(),
+        })
+    });
+    for message in &messages {
+        stream.write(message.clone()).unwrap();
+    }
+    while stream.has_pending() {
+        stream.flush_pending().unwrap();
+    }
+
+    for message in messages {
+        let mut buffer = [0u8; 128];
+        server.read_exact(&mut buffer).unwrap();
+        assert_eq!(chesstp::Message::parse_from(&buffer), Ok(message));
+    }
+}
+
+#[test]
+fn close_delivers_pending_quit() {
+    let (client, mut server) = loopback_pair();
+    let mut stream = ChesstpMessageStream::new(client).unwrap();
+
+    // The peer only starts reading after a while, like on a slow connection.
+    let reader = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        let mut received = Vec::new();
+        server.read_to_end(&mut received).unwrap();
+        received
+    });
+
+    // Fill the socket buffer so that the quit message has to be queued.
+    let filler = chesstp::Message::Quit(chesstp::QuitMessage {
+        message: "filler".to_owned(),
+    });
+    while !stream.has_pending() {
+        stream.write(filler.clone()).unwrap();
+    }
+    let quit = chesstp::Message::Quit(chesstp::QuitMessage {
+        message: "bye".to_owned(),
+    });
+    stream.write(quit.clone()).unwrap();
+    stream.close().unwrap();
+
+    let received = reader.join().unwrap();
+    let last_frame: &[u8; 128] = received[received.len() - 128..].try_into().unwrap();
+    assert_eq!(chesstp::Message::parse_from(last_frame), Ok(quit));
+}
+
+#[test]
+fn close_twice_peer_sees_eof() {
+    let (client, mut server) = loopback_pair();
+    let mut stream = ChesstpMessageStream::new(client).unwrap();
+
+    stream.close().unwrap();
+    // E.g. a second quit event.
+    stream.close().unwrap();
+
+    let mut received = Vec::new();
+    assert_eq!(server.read_to_end(&mut received).unwrap(), 0);
+}
+
+#[test]
+fn accept_split_frame() {
+    let (client, mut server) = loopback_pair();
+    let mut stream = ChesstpMessageStream::new(client).unwrap();
+
+    let message = chesstp::Message::Quit(chesstp::QuitMessage {
+        message: "split".to_owned(),
+    });
+    let frame = message.serialize();
+
+    server.write_all(&frame[..50]).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(stream.accept().unwrap(), None);
+
+    server.write_all(&frame[50..]).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(stream.accept().unwrap(), Some(message));
+}
+
+#[test]
+fn accept_stalled_frame_times_out() {
+    let (client, mut server) = loopback_pair();
+    let mut stream = ChesstpMessageStream::new(client).unwrap();
+    stream.set_frame_timeout(Some(Duration::from_millis(50)));
+
+    server.write_all(b"ChessQUIT:").unwrap();
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(stream.accept().unwrap(), None);
+
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(matches!(stream.accept(), Err(TransportError::Stalled(_))));
+    assert_eq!(stream.health(), ConnectionHealth::Stalled);
+}
+
+#[test]
+fn accept_skips_malformed_frame() {
+    let (client, mut server) = loopback_pair();
+    let mut stream = ChesstpMessageStream::new(client).unwrap();
+
+    let message = chesstp::Message::Quit(chesstp::QuitMessage {
+        message: "valid".to_owned(),
+    });
+
+    server.write_all(b"garbage ChessXX\xFF\xFE:").unwrap();
+    server.write_all(&message.serialize()).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(stream.take_discarded_frame_error(), None);
+    assert_eq!(stream.accept().unwrap(), Some(message));
+    assert!(stream.take_discarded_frame_error().is_some());
+    assert_eq!(stream.take_discarded_frame_error(), None);
+}
+
+#[test]
+fn next_frame_start_partial_prefix() {
+    assert_eq!(next_frame_start(b"ChessXXChessQUIT", b"Chess"), Some(7));
+    assert_eq!(next_frame_start(b"ChessXXXChe", b"Chess"), Some(8));
+    assert_eq!(next_frame_start(b"ChessXXX", b"Chess"), None);
+    assert_eq!(next_frame_start(b"SyncXXSyncQUIT", b"Sync"), Some(6));
+}
+
+#[test]
+fn accept_custom_prefix() {
+    let (mut client, server) = loopback_pair();
+    let mut stream = ChesstpMessageStream::with_prefix(server, "Sync").unwrap();
+
+    let message = chesstp::Message::Quit(chesstp::QuitMessage {
+        message: "bye".to_owned(),
+    });
+    // Frames with the default prefix aren't recognized.
+    client.write_all(&message.serialize()).unwrap();
+    client
+        .write_all(&message.serialize_with_prefix("Sync"))
+        .unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(stream.accept().unwrap(), Some(message));
+    assert_eq!(stream.accept().unwrap(), None);
+}
+
+#[test]
+fn invalid_prefix_rejected() {
+    let (client, server) = loopback_pair();
+
+    assert!(ChesstpMessageStream::with_prefix(client, "").is_err());
+    assert!(ChesstpMessageStream::with_prefix(server, "Ch:ess").is_err());
+}
+
+#[test]
+fn prefix_longer_than_frame_budget_rejected() {
+    let (client, server) = loopback_pair();
+
+    let longest = "X".repeat(chesstp::MAX_PREFIX_LEN);
+    assert!(ChesstpMessageStream::with_prefix(client, &longest).is_ok());
+    let too_long = "X".repeat(chesstp::MAX_PREFIX_LEN + 1);
+    assert!(ChesstpMessageStream::with_prefix(server, &too_long).is_err());
+}
+
+#[test]
+fn health_peer_closed() {
+    let (client, server) = loopback_pair();
+    let mut stream = ChesstpMessageStream::new(client).unwrap();
+
+    assert_eq!(stream.accept().unwrap(), None);
+    assert_eq!(stream.health(), ConnectionHealth::Healthy);
+
+    drop(server);
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(stream.accept().unwrap(), None);
+    assert_eq!(stream.health(), ConnectionHealth::PeerClosed);
+}
+
+#[test]
+fn discovery_announcement_round_trip() {
+    let sender: SocketAddr = "192.168.1.20:45000".parse().unwrap();
+    let announcement = discovery::serialize_announcement("Alice's game: rematch", 3000);
+
+    assert_eq!(
+        discovery::parse_announcement(announcement.as_bytes(), sender),
+        Some(discovery::DiscoveredServer {
+            name: "Alice's game: rematch".to_owned(),
+            addr: "192.168.1.20:3000".parse().unwrap(),
+        })
+    );
+}
+
+#[test]
+fn discovery_announcement_invalid() {
+    let sender: SocketAddr = "192.168.1.20:45000".parse().unwrap();
+
+    assert_eq!(
+        discovery::parse_announcement(b"ChessHOST:abc:name", sender),
+        None
+    );
+    assert_eq!(
+        discovery::parse_announcement(b"ChessMOVE:3000:name", sender),
+        None
+    );
+    assert_eq!(discovery::parse_announcement(b"\xFF\xFE", sender), None);
+}
@@ -3,7 +3,48 @@ use std::sync::LazyLock;
 use colors_transform::{AlphaColor, Color, Rgb};
 use ggez::graphics;
 
+#[cfg(test)]
+mod tests;
+
+/// How the coordinate labels on the board squares are colored.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LabelColor {
+    /// The color of the opposite squares, e.g. dark labels on light squares.
+    OppositeSquare,
+    Fixed(graphics::Color),
+    /// Black or white, whichever contrasts more with the square, see `contrasting_text_color`.
+    Auto,
+}
+
+/// Returns black or white, whichever contrasts more with `background` according to its relative
+/// luminance.
+pub fn contrasting_text_color(background: graphics::Color) -> graphics::Color {
+    fn linearize(channel: f32) -> f32 {
+        if channel <= 0.04045 {
+            channel / 12.92
+        } else {
+            ((channel + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    let luminance = 0.2126 * linearize(background.r)
+        + 0.7152 * linearize(background.g)
+        + 0.0722 * linearize(background.b);
+
+    // Above this, the contrast ratio to black is higher than to white.
+    if luminance > 0.179 {
+        graphics::Color::BLACK
+    } else {
+        graphics::Color::WHITE
+    }
+}
+
 pub struct Palette {
+    /// Fills the window behind the scene.
+    pub window_background: graphics::Color,
+    /// If set, the window background fades from `window_background` at the top to this color at
+    /// the bottom instead of being flat.
+    pub window_background_gradient: Option<graphics::Color>,
     pub piece_white: graphics::Color,
     pub piece_black: graphics::Color,
     pub board_square_black: graphics::Color,
@@ -13,12 +54,31 @@ pub struct Palette {
     pub board_square_white_hover: graphics::Color,
     pub board_square_white_active: graphics::Color,
     pub board_square_selected: graphics::Color,
-    pub highlight_subtle_overlay: graphics::Color,
+    /// The color of the rank and file labels.
+    pub board_label: LabelColor,
+    /// Drawn over the square of the piece which is being moved.
+    pub selection_highlight: graphics::Color,
+    /// Drawn over the source and destination squares of the last move.
+    pub last_move_highlight: graphics::Color,
+    /// Drawn over the destination of a move waiting to be confirmed.
+    pub staged_move_highlight: graphics::Color,
+    /// Drawn over the king of the side to move while it's in check.
+    pub check_highlight: graphics::Color,
+    pub board_frame: graphics::Color,
+    /// Flashed behind the turn indicator when it becomes the local player's turn.
+    pub turn_flash: graphics::Color,
+    pub threatened_square_overlay: graphics::Color,
+    pub checking_move_overlay: graphics::Color,
+    pub capture_target_overlay: graphics::Color,
+    pub quiet_move_overlay: graphics::Color,
     pub button: graphics::Color,
     pub button_hover: graphics::Color,
     pub button_active: graphics::Color,
     pub text_subtle: graphics::Color,
     pub text_neutral: graphics::Color,
+    pub text_warning: graphics::Color,
+    pub modal_backdrop: graphics::Color,
+    pub modal_background: graphics::Color,
 }
 
 impl Default for Palette {
@@ -33,23 +93,48 @@ impl Default for Palette {
         let board_square_white = Rgb::from_hex_str("#ede1d1").unwrap();
 
         Self {
+            window_background: graphics::Color::from_rgb_u32(0x2E2B28),
+            window_background_gradient: None,
             piece_white: convert_color(Rgb::from_hex_str("#ffe7c4").unwrap()),
             piece_black: convert_color(Rgb::from_hex_str("#636363").unwrap()),
             board_square_black: convert_color(board_square_black),
-            board_square_black_hover: convert_color(board_square_black),
+            board_square_black_hover: convert_color(board_square_black.lighten(6.0)),
             board_square_black_active: convert_color(board_square_black.lighten(-10.0)),
             board_square_white: convert_color(board_square_white),
-            board_square_white_hover: convert_color(board_square_white),
+            board_square_white_hover: convert_color(board_square_white.lighten(4.0)),
             board_square_white_active: convert_color(board_square_white.lighten(-10.0)),
             board_square_selected: convert_color(Rgb::from_hex_str("#e9da57").unwrap()),
-            highlight_subtle_overlay: convert_color(
-                Rgb::from_hex_str("#000000").unwrap().set_alpha(0.3),
+            board_label: LabelColor::OppositeSquare,
+            selection_highlight: convert_color(
+                Rgb::from_hex_str("#e9da57").unwrap().set_alpha(0.7),
+            ),
+            last_move_highlight: convert_color(
+                Rgb::from_hex_str("#e9da57").unwrap().set_alpha(0.7),
+            ),
+            staged_move_highlight: convert_color(
+                Rgb::from_hex_str("#6fa8dc").unwrap().set_alpha(0.7),
+            ),
+            check_highlight: convert_color(Rgb::from_hex_str("#e74c3c").unwrap().set_alpha(0.6)),
+            board_frame: convert_color(Rgb::from_hex_str("#4a4641").unwrap()),
+            turn_flash: convert_color(Rgb::from_hex_str("#e9da57").unwrap().set_alpha(0.35)),
+            threatened_square_overlay: convert_color(
+                Rgb::from_hex_str("#d9534f").unwrap().set_alpha(0.35),
+            ),
+            checking_move_overlay: convert_color(
+                Rgb::from_hex_str("#c0392b").unwrap().set_alpha(0.6),
+            ),
+            capture_target_overlay: convert_color(
+                Rgb::from_hex_str("#a5443a").unwrap().set_alpha(0.45),
             ),
+            quiet_move_overlay: convert_color(Rgb::from_hex_str("#000000").unwrap().set_alpha(0.3)),
             button: convert_color(Rgb::from_hex_str("#22211e").unwrap()),
             button_hover: convert_color(Rgb::from_hex_str("#393734").unwrap()),
             button_active: convert_color(Rgb::from_hex_str("#1b1a18").unwrap()),
             text_subtle: convert_color(Rgb::from_hex_str("#aea696").unwrap()),
             text_neutral: convert_color(Rgb::from_hex_str("#cbc1b4").unwrap()),
+            text_warning: convert_color(Rgb::from_hex_str("#e8a33d").unwrap()),
+            modal_backdrop: convert_color(Rgb::from_hex_str("#000000").unwrap().set_alpha(0.5)),
+            modal_background: convert_color(Rgb::from_hex_str("#3b3834").unwrap()),
         }
     }
 }
@@ -0,0 +1,32 @@
+use super::*;
+
+#[test]
+fn contrasting_text_color_extremes() {
+    assert_eq!(
+        contrasting_text_color(graphics::Color::WHITE),
+        graphics::Color::BLACK
+    );
+    assert_eq!(
+        contrasting_text_color(graphics::Color::BLACK),
+        graphics::Color::WHITE
+    );
+}
+
+#[test]
+fn contrasting_text_color_board_squares() {
+    let palette = Palette::default();
+
+    // Both squares of the default palette are light.
+    assert_eq!(
+        contrasting_text_color(palette.board_square_white),
+        graphics::Color::BLACK
+    );
+    assert_eq!(
+        contrasting_text_color(palette.board_square_black),
+        graphics::Color::BLACK
+    );
+    assert_eq!(
+        contrasting_text_color(palette.board_frame),
+        graphics::Color::WHITE
+    );
+}
@@ -0,0 +1,233 @@
+//! Modal asking the user to confirm leaving a networked game, since closing the window resigns it.
+
+use std::{cell::RefCell, sync::Arc};
+
+use ggez::{Context, GameResult, glam, graphics};
+
+use crate::{
+    network::chesstp,
+    palette::PALETTE,
+    typography,
+    ui::{self, PressState},
+};
+
+#[cfg(test)]
+mod tests;
+
+static DIALOG_WIDTH: f32 = 500.0;
+static DIALOG_HEIGHT: f32 = 270.0;
+/// Most characters the reason may have, so that it fits in the dialog. How many bytes it may have
+/// is limited separately, by what fits in a chesstp frame.
+static MAX_REASON_LEN: usize = 40;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum QuitChoice {
+    ResignAndQuit,
+    Cancel,
+}
+
+pub struct QuitDialog {
+    /// The area which is dimmed behind the dialog.
+    backdrop_bounds: graphics::Rect,
+    bounds: graphics::Rect,
+    /// Shown to the opponent when leaving, typed in by the user.
+    reason: String,
+    choice: Arc<RefCell<Option<QuitChoice>>>,
+    resign_button: ui::Button,
+    cancel_button: ui::Button,
+}
+
+impl QuitDialog {
+    /// Create a dialog centered over a scene of the specified size.
+    pub fn new(scene_size: glam::Vec2) -> Self {
+        let bounds = graphics::Rect {
+            x: scene_size.x / 2.0 - DIALOG_WIDTH / 2.0,
+            y: scene_size.y / 2.0 - DIALOG_HEIGHT / 2.0,
+            w: DIALOG_WIDTH,
+            h: DIALOG_HEIGHT,
+        };
+        let choice = Arc::new(RefCell::new(None));
+
+        let button_colors =
+            ui::MouseColors::new(PALETTE.button, PALETTE.button_hover, PALETTE.button_active);
+        let button_top = bounds.bottom() - 60.0 - 30.0;
+
+        let resign_choice = choice.clone();
+        let resign_button = ui::Button::new(
+            graphics::Rect {
+                x: bounds.x + 30.0,
+                y: button_top,
+                w: 210.0,
+                h: 60.0,
+            },
+            ui::RoundedButton::new(10.0, button_colors, move || {
+                *resign_choice.borrow_mut() = Some(QuitChoice::ResignAndQuit);
+            }),
+        );
+
+        let cancel_choice = choice.clone();
+        let cancel_button = ui::Button::new(
+            graphics::Rect {
+                x: bounds.right() - 30.0 - 210.0,
+                y: button_top,
+                w: 210.0,
+                h: 60.0,
+            },
+            ui::RoundedButton::new(10.0, button_colors, move || {
+                *cancel_choice.borrow_mut() = Some(QuitChoice::Cancel);
+            }),
+        );
+
+        Self {
+            backdrop_bounds: graphics::Rect::new(0.0, 0.0, scene_size.x, scene_size.y),
+            bounds,
+            reason: String::new(),
+            choice,
+            resign_button,
+            cancel_button,
+        }
+    }
+
+    /// Returns the button the user pressed, if any.
+    pub fn choice(&self) -> Option<QuitChoice> {
+        *self.choice.borrow()
+    }
+
+    /// The reason for leaving typed in so far.
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    /// Append a typed character to the reason, ignoring control characters such as backspace, and
+    /// characters which wouldn't fit.
+    pub fn type_char(&mut self, character: char) {
+        let max_bytes = chesstp::max_quit_message_len(chesstp::DEFAULT_PREFIX);
+        if !character.is_control()
+            && self.reason.chars().count() < MAX_REASON_LEN
+            && self.reason.len() + character.len_utf8() <= max_bytes
+        {
+            self.reason.push(character);
+        }
+    }
+
+    /// Remove the last character of the reason.
+    pub fn backspace(&mut self) {
+        self.reason.pop();
+    }
+
+    pub fn update_with_press_state(
+        &mut self,
+        position: glam::Vec2,
+        press_state: PressState,
+    ) -> bool {
+        for button in [&mut self.resign_button, &mut self.cancel_button] {
+            if button.update_with_press_state(position, press_state) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    pub fn update_with_mouse_position(&mut self, position: glam::Vec2) {
+        for button in [&mut self.resign_button, &mut self.cancel_button] {
+            button.update_with_mouse_position(position);
+        }
+    }
+
+    pub fn draw(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        offset: glam::Vec2,
+    ) -> GameResult {
+        let mut backdrop_bounds = self.backdrop_bounds;
+        backdrop_bounds.translate(offset);
+        let backdrop = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            backdrop_bounds,
+            PALETTE.modal_backdrop,
+        )?;
+        canvas.draw(&backdrop, graphics::DrawParam::new());
+
+        let mut bounds = self.bounds;
+        bounds.translate(offset);
+        let background = graphics::Mesh::new_rounded_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            bounds,
+            15.0,
+            PALETTE.modal_background,
+        )?;
+        canvas.draw(&background, graphics::DrawParam::new());
+
+        let mut title = graphics::Text::new("Leave the game?");
+        title
+            .set_scale(graphics::PxScale::from(typography::SUBHEADING))
+            .set_layout(graphics::TextLayout {
+                h_align: graphics::TextAlign::Middle,
+                v_align: graphics::TextAlign::Begin,
+            });
+        canvas.draw(
+            &title,
+            graphics::DrawParam::new()
+                .color(PALETTE.text_neutral)
+                .dest(glam::vec2(bounds.center().x, bounds.top() + 30.0)),
+        );
+
+        // Draw the reason field.
+        let field_bounds = graphics::Rect {
+            x: bounds.x + 30.0,
+            y: bounds.top() + 85.0,
+            w: bounds.w - 60.0,
+            h: 50.0,
+        };
+        let field = graphics::Mesh::new_rounded_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            field_bounds,
+            10.0,
+            PALETTE.button,
+        )?;
+        canvas.draw(&field, graphics::DrawParam::new());
+
+        let (reason, reason_color) = if self.reason.is_empty() {
+            ("Reason (optional)", PALETTE.text_subtle)
+        } else {
+            (self.reason.as_str(), PALETTE.text_neutral)
+        };
+        let mut reason_text = graphics::Text::new(reason);
+        reason_text
+            .set_scale(graphics::PxScale::from(typography::SMALL))
+            .set_layout(graphics::TextLayout {
+                h_align: graphics::TextAlign::Begin,
+                v_align: graphics::TextAlign::Middle,
+            });
+        canvas.draw(
+            &reason_text,
+            graphics::DrawParam::new()
+                .color(reason_color)
+                .dest(glam::vec2(field_bounds.x + 15.0, field_bounds.center().y)),
+        );
+
+        for (button, label) in [
+            (&self.resign_button, "Resign & quit"),
+            (&self.cancel_button, "Cancel"),
+        ] {
+            button.draw(ctx, canvas, offset)?;
+
+            let mut text = graphics::Text::new(label);
+            text.set_scale(graphics::PxScale::from(typography::BODY))
+                .set_layout(graphics::TextLayout::center());
+            canvas.draw(
+                &text,
+                graphics::DrawParam::new().color(PALETTE.text_neutral).dest(
+                    glam::vec2(button.bounds().center().x, button.bounds().center().y) + offset,
+                ),
+            );
+        }
+
+        Ok(())
+    }
+}
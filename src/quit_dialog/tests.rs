@@ -0,0 +1,29 @@
+use super::*;
+
+#[test]
+fn type_char_limits_characters() {
+    let mut dialog = QuitDialog::new(glam::vec2(800.0, 600.0));
+    for _ in 0..50 {
+        dialog.type_char('a');
+    }
+
+    assert_eq!(dialog.reason().len(), MAX_REASON_LEN);
+}
+
+#[test]
+fn type_char_limits_bytes() {
+    let mut dialog = QuitDialog::new(glam::vec2(800.0, 600.0));
+    for _ in 0..MAX_REASON_LEN {
+        dialog.type_char('🙂');
+    }
+
+    // 29 four byte characters fit in the 117 bytes left in the frame.
+    assert_eq!(dialog.reason().chars().count(), 29);
+    let message = chesstp::Message::Quit(chesstp::QuitMessage {
+        message: dialog.reason().to_owned(),
+    });
+    assert_eq!(
+        chesstp::Message::parse_from(&message.serialize()),
+        Ok(message)
+    );
+}
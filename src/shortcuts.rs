@@ -0,0 +1,104 @@
+//! The keyboard shortcuts of the game, which both the key handlers and the help overlay are driven
+//! by so that the documented bindings can't drift from the actual ones.
+
+use ggez::input::keyboard::{KeyCode, KeyMods};
+
+#[cfg(test)]
+mod tests;
+
+/// Something a shortcut does.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    FlipBoard,
+    ToggleCompact,
+    ToggleBlindfold,
+    /// Is active while the key is held.
+    RevealPieces,
+    OfferOrAcceptDraw,
+    Resign,
+    UndoMove,
+    CancelOrQuit,
+    ToggleHelp,
+    TogglePerformance,
+}
+
+impl Action {
+    /// Shown next to the key in the help overlay.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::FlipBoard => "Flip the board",
+            Action::ToggleCompact => "Toggle the compact layout",
+            Action::ToggleBlindfold => "Toggle blindfold mode",
+            Action::RevealPieces => "Hold to show the pieces in blindfold mode",
+            Action::OfferOrAcceptDraw => "Offer, accept or claim a draw",
+            Action::Resign => "Resign",
+            Action::UndoMove => "Take back the last move (local games)",
+            Action::CancelOrQuit => "Cancel the staged move, or quit",
+            Action::ToggleHelp => "Show or hide this help (also ?)",
+            Action::TogglePerformance => "Toggle the frame rate overlay",
+        }
+    }
+
+    /// Whether the action lasts while its key is held, and ends when the key is released.
+    pub fn is_held(&self) -> bool {
+        matches!(self, Action::RevealPieces)
+    }
+}
+
+/// A key combined with the modifiers which have to be held along with it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Shortcut {
+    pub key: KeyCode,
+    pub ctrl: bool,
+}
+
+impl Shortcut {
+    const fn key(key: KeyCode) -> Self {
+        Self { key, ctrl: false }
+    }
+
+    const fn ctrl(key: KeyCode) -> Self {
+        Self { key, ctrl: true }
+    }
+
+    /// The name of the shortcut as shown in the help overlay, e.g. "F1" or "Ctrl+Z".
+    pub fn label(&self) -> String {
+        if self.ctrl {
+            format!("Ctrl+{:?}", self.key)
+        } else {
+            format!("{:?}", self.key)
+        }
+    }
+}
+
+/// Every shortcut, in the order they're listed in the help overlay.
+pub static BINDINGS: &[(Shortcut, Action)] = &[
+    (Shortcut::key(KeyCode::F), Action::FlipBoard),
+    (Shortcut::key(KeyCode::C), Action::ToggleCompact),
+    (Shortcut::key(KeyCode::B), Action::ToggleBlindfold),
+    (Shortcut::key(KeyCode::Space), Action::RevealPieces),
+    (Shortcut::key(KeyCode::D), Action::OfferOrAcceptDraw),
+    (Shortcut::key(KeyCode::R), Action::Resign),
+    (Shortcut::ctrl(KeyCode::Z), Action::UndoMove),
+    (Shortcut::key(KeyCode::Escape), Action::CancelOrQuit),
+    (Shortcut::key(KeyCode::F1), Action::ToggleHelp),
+    (Shortcut::key(KeyCode::F3), Action::TogglePerformance),
+];
+
+/// Returns the action bound to `key` pressed while holding `mods`, if any.
+pub fn action_for(key: KeyCode, mods: KeyMods) -> Option<Action> {
+    let ctrl = mods.contains(KeyMods::CTRL);
+    BINDINGS
+        .iter()
+        .find(|(shortcut, _)| *shortcut == Shortcut { key, ctrl })
+        .map(|&(_, action)| action)
+}
+
+/// Returns the action bound to `key` which is active while it's held, if any. Ignores the
+/// modifiers, so that releasing the key ends the action even if a modifier was pressed meanwhile.
+pub fn held_action_for(key: KeyCode) -> Option<Action> {
+    BINDINGS
+        .iter()
+        .find(|(shortcut, action)| shortcut.key == key && action.is_held())
+        .map(|&(_, action)| action)
+}
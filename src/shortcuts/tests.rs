@@ -0,0 +1,43 @@
+use std::collections::HashSet;
+
+use super::*;
+
+#[test]
+fn keys_bound_once() {
+    let keys = BINDINGS
+        .iter()
+        .map(|&(shortcut, _)| (shortcut.key, shortcut.ctrl))
+        .collect::<HashSet<_>>();
+
+    assert_eq!(keys.len(), BINDINGS.len());
+}
+
+#[test]
+fn action_for_bound_and_unbound_keys() {
+    assert_eq!(
+        action_for(KeyCode::F1, KeyMods::empty()),
+        Some(Action::ToggleHelp)
+    );
+    assert_eq!(
+        action_for(KeyCode::Escape, KeyMods::empty()),
+        Some(Action::CancelOrQuit)
+    );
+    assert_eq!(action_for(KeyCode::Q, KeyMods::empty()), None);
+}
+
+#[test]
+fn action_for_ctrl_shortcut() {
+    assert_eq!(
+        action_for(KeyCode::Z, KeyMods::CTRL),
+        Some(Action::UndoMove)
+    );
+    assert_eq!(action_for(KeyCode::Z, KeyMods::empty()), None);
+    assert_eq!(Shortcut::ctrl(KeyCode::Z).label(), "Ctrl+Z");
+}
+
+#[test]
+fn held_action_for_ignores_modifiers() {
+    assert_eq!(held_action_for(KeyCode::Space), Some(Action::RevealPieces));
+    // Only actions which last while the key is held end on release.
+    assert_eq!(held_action_for(KeyCode::F), None);
+}
@@ -0,0 +1,10 @@
+//! The type scale which all text sizes are picked from, so that the UI stays consistent.
+
+/// Large titles, e.g. the side to move and the game-over banner.
+pub static HEADING: f32 = 80.0;
+/// Player labels and dialog titles.
+pub static SUBHEADING: f32 = 35.0;
+/// Most other text, e.g. subtitles, buttons, coordinates and connection info.
+pub static BODY: f32 = 30.0;
+/// Dense text, e.g. the move list and input fields.
+pub static SMALL: f32 = 24.0;
@@ -72,6 +72,9 @@ impl Button {
     pub fn bounds(&self) -> Rect {
         self.bounds
     }
+    pub fn set_bounds(&mut self, bounds: Rect) {
+        self.bounds = bounds;
+    }
     pub fn press_state(&self) -> PressState {
         self.press_state
     }